@@ -0,0 +1,67 @@
+use std::time::{Duration, Instant};
+use bevy::ecs::system::CommandQueue;
+use bevy::prelude::*;
+
+use space_shooter::pool::{self, EntityPool};
+
+const FRAMES: usize = 600;
+const BULLETS_PER_FRAME: usize = 200;
+const BULLET_LIFETIME_FRAMES: usize = 90;
+
+#[derive(Component)]
+struct StressBullet;
+
+type StressPool = EntityPool<(StressBullet,)>;
+
+//bullet-hell pattern: every frame fire a ring of bullets, release the ones older than the lifetime
+fn run(pooled: bool) -> (Duration, pool::PoolMetrics){
+    let mut world = World::new();
+    let mut pool = StressPool::default();
+    pool.enabled = pooled;
+
+    let mut alive: Vec<Vec<Entity>> = Vec::new();
+    let start = Instant::now();
+
+    for frame in 0..FRAMES{
+        let mut queue = CommandQueue::default();
+        let mut commands = Commands::new(&mut queue, &world);
+
+        if frame >= BULLET_LIFETIME_FRAMES{
+            for entity in alive.remove(0){
+                pool.release(&mut commands, entity);
+            }
+        }
+
+        let mut fired = Vec::with_capacity(BULLETS_PER_FRAME);
+        for i in 0..BULLETS_PER_FRAME{
+            let angle = i as f32 / BULLETS_PER_FRAME as f32 * std::f32::consts::TAU;
+            let bullet = pool.acquire(&mut commands);
+            commands.entity(bullet).insert_bundle(SpriteBundle{
+                sprite: Sprite{
+                    custom_size: Some(Vec2::new(10., 10.)),
+                    ..default()
+                },
+                transform: Transform::from_xyz(angle.cos() * 20., angle.sin() * 20., 1.),
+                ..default()
+            })
+            .insert(StressBullet);
+            fired.push(bullet);
+        }
+        alive.push(fired);
+
+        queue.apply(&mut world);
+        pool.recycle();
+    }
+
+    (start.elapsed(), pool.metrics())
+}
+
+fn main(){
+    for (name, pooled) in [("unpooled", false), ("pooled", true)]{
+        let (elapsed, metrics) = run(pooled);
+        println!(
+            "{:<9} {:>8.2} ms  ({} frames x {} bullets)  size: {}  high water mark: {}",
+            name, elapsed.as_secs_f64() * 1000., FRAMES, BULLETS_PER_FRAME, metrics.size, metrics.high_water_mark
+        );
+    }
+}
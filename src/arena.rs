@@ -0,0 +1,103 @@
+use bevy::prelude::*;
+use crate::components::{HudRoot, MainCamera};
+use crate::{ARENA_HEIGHT, ARENA_WIDTH, BACKGROUND_COLOR};
+
+const LETTERBOX_Z_LAYER: f32 = 900.;
+const LETTERBOX_SIZE: f32 = 10000.;
+
+pub struct ArenaPlugin;
+
+impl Plugin for ArenaPlugin{
+    fn build(&self, app: &mut App) {
+        app
+        .insert_resource(ArenaViewport::default())
+        .add_startup_system(spawn_letterbox)
+        .add_system(fit_arena_to_window);
+    }
+}
+
+//Where the logical arena ends up in the window, in logical window pixels
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ArenaViewport{
+    pub scale: f32,
+    pub offset: Vec2,
+    pub size: Vec2,
+}
+
+impl Default for ArenaViewport{
+    fn default() -> Self {
+        Self::fit(Vec2::new(ARENA_WIDTH, ARENA_HEIGHT))
+    }
+}
+
+impl ArenaViewport{
+    //largest uniform scale that fits the whole arena, the rest of the window is letterboxed
+    pub fn fit(window_size: Vec2) -> Self{
+        let scale = (window_size.x / ARENA_WIDTH).min(window_size.y / ARENA_HEIGHT).max(f32::EPSILON);
+        let size = Vec2::new(ARENA_WIDTH, ARENA_HEIGHT) * scale;
+
+        Self { scale, offset: (window_size - size) / 2., size }
+    }
+}
+
+//black bars covering everything outside the arena, so objects entering from off-screen stay hidden
+fn spawn_letterbox(mut commands: Commands){
+    let half_arena = Vec2::new(ARENA_WIDTH, ARENA_HEIGHT) / 2.;
+    let half_bar = LETTERBOX_SIZE / 2.;
+
+    let bars = [
+        Vec2::new(0., half_arena.y + half_bar),
+        Vec2::new(0., -half_arena.y - half_bar),
+        Vec2::new(half_arena.x + half_bar, 0.),
+        Vec2::new(-half_arena.x - half_bar, 0.),
+    ];
+
+    for position in bars{
+        commands.spawn_bundle(SpriteBundle{
+            sprite: Sprite{
+                color: BACKGROUND_COLOR,
+                custom_size: Some(Vec2::splat(LETTERBOX_SIZE)),
+                ..default()
+            },
+            transform: Transform::from_translation(position.extend(LETTERBOX_Z_LAYER)),
+            ..default()
+        });
+    }
+}
+
+fn fit_arena_to_window(windows: Res<Windows>, mut viewport: ResMut<ArenaViewport>,
+    mut camera_query: Query<&mut OrthographicProjection, With<MainCamera>>,
+    mut hud_query: Query<&mut Style, With<HudRoot>>){
+
+    let window = match windows.get_primary(){
+        Some(window) => window,
+        None => return,
+    };
+
+    let fitted = ArenaViewport::fit(Vec2::new(window.width(), window.height()));
+    if *viewport != fitted{
+        *viewport = fitted;
+    }
+
+    //only write when different, both trigger a relayout on change
+    for mut projection in camera_query.iter_mut(){
+        let scale = 1. / viewport.scale;
+        if projection.scale != scale{
+            projection.scale = scale;
+        }
+    }
+
+    let position = Rect {
+        left: Val::Px(viewport.offset.x),
+        bottom: Val::Px(viewport.offset.y),
+        ..default()
+    };
+    let size = Size::new(Val::Px(viewport.size.x), Val::Px(viewport.size.y));
+
+    for mut style in hud_query.iter_mut(){
+        if style.position != position || style.size != size{
+            style.position = position;
+            style.size = size;
+        }
+    }
+}
@@ -0,0 +1,202 @@
+use bevy::prelude::*;
+use bevy::asset::LoadState;
+use crate::components::{GameState, LoadingScreen, LoadingText, ProgressBarFill};
+use crate::BACKGROUND_COLOR;
+
+const FONT_PATH: &str = "fonts/FiraSans-Regular.ttf";
+const PROGRESS_BAR_SIZE: (f32, f32) = (300., 16.);
+
+//Every asset the game needs before gameplay can start
+pub const ASSET_MANIFEST: &[&str] = &[
+    "images/player/player.png",
+    "images/player/player_health_icon.png",
+    "images/player/shield.png",
+    "images/enemy/enemy.png",
+    "images/explosion.png",
+    "images/asteroid.png",
+    FONT_PATH,
+];
+
+//only loaded when the game is built with audio, there is no loader for them otherwise
+pub const AUDIO_MANIFEST: &[&str] = &[
+    "sounds/player_shot.wav",
+    "sounds/enemy_shot.wav",
+    "sounds/hit.wav",
+    "sounds/explosion.wav",
+    "sounds/pickup.wav",
+    "music/menu.wav",
+    "music/gameplay.wav",
+    "music/game_over.wav",
+];
+
+pub struct AssetLoadingPlugin;
+
+impl Plugin for AssetLoadingPlugin{
+    fn build(&self, app: &mut App) {
+        app
+        .add_system_set(
+            SystemSet::on_enter(GameState::Loading)
+            .with_system(start_loading)
+            .with_system(spawn_loading_screen)
+        )
+        .add_system_set(
+            SystemSet::on_update(GameState::Loading)
+            .with_system(check_loading)
+        )
+        .add_system_set(
+            SystemSet::on_exit(GameState::Loading)
+            .with_system(despawn_screen)
+        )
+        .add_system_set(
+            SystemSet::on_enter(GameState::AssetError)
+            .with_system(spawn_error_screen)
+        );
+    }
+}
+
+pub struct LoadingAssets(pub Vec<(&'static str, HandleUntyped)>);
+
+//paths of the assets that are missing or could not be decoded
+pub struct FailedAssets(pub Vec<&'static str>);
+
+fn start_loading(mut commands: Commands, asset_server: Res<AssetServer>){
+    let mut paths = ASSET_MANIFEST.to_vec();
+    if cfg!(feature = "audio"){
+        paths.extend(AUDIO_MANIFEST);
+    }
+
+    let handles = paths.into_iter()
+        .map(|path| (path, asset_server.load_untyped(path)))
+        .collect();
+
+    commands.insert_resource(LoadingAssets(handles));
+}
+
+fn check_loading(mut commands: Commands, asset_server: Res<AssetServer>, loading: Res<LoadingAssets>, mut state: ResMut<State<GameState>>,
+    mut progress_query: Query<&mut Style, With<ProgressBarFill>>, mut text_query: Query<&mut Text, With<LoadingText>>){
+
+    let mut loaded = 0;
+    let mut failed = Vec::new();
+
+    for (path, handle) in loading.0.iter(){
+        match asset_server.get_load_state(handle){
+            LoadState::Loaded => loaded += 1,
+            LoadState::Failed => failed.push(*path),
+            _ => {}
+        }
+    }
+
+    let total = loading.0.len().max(1);
+    for mut style in progress_query.iter_mut(){
+        style.size.width = Val::Percent(loaded as f32 / total as f32 * 100.);
+    }
+    for mut text in text_query.iter_mut(){
+        text.sections[0].value = format!("Loading {}/{}", loaded, total);
+    }
+
+    //wait for everything to settle so the error screen lists all the broken assets at once
+    if !failed.is_empty() && loaded + failed.len() == loading.0.len(){
+        for path in failed.iter(){
+            error!("missing or malformed asset: {}", path);
+        }
+        commands.insert_resource(FailedAssets(failed));
+        let _ = state.set(GameState::AssetError);
+    }else if loaded == loading.0.len(){
+        let _ = state.set(GameState::MainMenu);
+    }
+}
+
+fn spawn_loading_screen(mut commands: Commands, asset_server: Res<AssetServer>){
+    commands
+    .spawn_bundle(NodeBundle{
+        style: Style{
+            size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+            flex_direction: FlexDirection::ColumnReverse,
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..default()
+        },
+        color: UiColor(BACKGROUND_COLOR),
+        ..default()
+    })
+    .insert(LoadingScreen)
+    .with_children(|parent| {
+        parent
+        .spawn_bundle(TextBundle{
+            text: Text::with_section(
+                "Loading",
+                TextStyle {
+                    font_size: 24.0,
+                    color: Color::WHITE,
+                    font: asset_server.load(FONT_PATH),
+                },
+                default()
+            ),
+            ..default()
+        })
+        .insert(LoadingText);
+
+        parent
+        .spawn_bundle(NodeBundle{
+            style: Style{
+                size: Size::new(Val::Px(PROGRESS_BAR_SIZE.0), Val::Px(PROGRESS_BAR_SIZE.1)),
+                margin: Rect::all(Val::Px(10.)),
+                ..default()
+            },
+            color: UiColor(Color::rgb(0.2, 0.2, 0.2)),
+            ..default()
+        })
+        .with_children(|parent| {
+            parent
+            .spawn_bundle(NodeBundle{
+                style: Style{
+                    size: Size::new(Val::Percent(0.), Val::Percent(100.)),
+                    ..default()
+                },
+                color: UiColor(Color::GOLD),
+                ..default()
+            })
+            .insert(ProgressBarFill);
+        });
+    });
+}
+
+fn spawn_error_screen(mut commands: Commands, asset_server: Res<AssetServer>, failed: Res<FailedAssets>){
+    let font = asset_server.load(FONT_PATH);
+
+    //the font itself may be the broken asset, the message is also logged in check_loading
+    let message = format!("Could not load the following assets:\n{}", failed.0.join("\n"));
+
+    commands
+    .spawn_bundle(NodeBundle{
+        style: Style{
+            size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..default()
+        },
+        color: UiColor(BACKGROUND_COLOR),
+        ..default()
+    })
+    .insert(LoadingScreen)
+    .with_children(|parent| {
+        parent.spawn_bundle(TextBundle{
+            text: Text::with_section(
+                message,
+                TextStyle {
+                    font_size: 20.0,
+                    color: Color::rgb(1.0, 0.4, 0.4),
+                    font,
+                },
+                default()
+            ),
+            ..default()
+        });
+    });
+}
+
+fn despawn_screen(mut commands: Commands, query: Query<Entity, With<LoadingScreen>>){
+    for entity in query.iter(){
+        commands.entity(entity).despawn_recursive();
+    }
+}
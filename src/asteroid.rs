@@ -0,0 +1,215 @@
+use bevy::prelude::*;
+use bevy::sprite::collide_aabb::collide;
+use rand::Rng;
+use crate::components::{Asteroid, AsteroidSize, Bullet, BulletPool, EffectKind, ExplosionToSpawn, GameRng, GameState, GameTextures, Health, Invulnerable, MovableObject, Player, PlayerDied, PlayerHit, Spin, SpriteSize, Velocity, Wave};
+use crate::{ARENA_HEIGHT, ARENA_WIDTH};
+use crate::player::ShieldedPlayer;
+use crate::utils::{ScaledTime, every, run_if_playing};
+
+const ASTEROID_Z_LAYER: f32 = 8.;
+const ASTEROID_SPAWN_CHANCE: f64 = 0.15;
+const ASTEROID_MAX_SPIN: f32 = 1.5;
+//sideways speed the pieces of a broken asteroid fly apart with
+const ASTEROID_SPLIT_SPEED: f32 = 0.15;
+
+//Asteroids placed at the start of a wave, as (wave, [(x, size)])
+const WAVE_ASTEROIDS: &[(u32, &[(f32, AsteroidSize)])] = &[
+    (2, &[(-150., AsteroidSize::Large), (150., AsteroidSize::Large)]),
+    (3, &[(-200., AsteroidSize::Medium), (0., AsteroidSize::Large), (200., AsteroidSize::Medium)]),
+    (5, &[(-220., AsteroidSize::Large), (-75., AsteroidSize::Medium), (75., AsteroidSize::Medium), (220., AsteroidSize::Large)]),
+];
+
+pub struct AsteroidPlugin;
+
+impl Plugin for AsteroidPlugin{
+    fn build(&self, app: &mut App) {
+        app
+        .add_system_set(
+            SystemSet::new()
+            .with_run_criteria(every(1.).chain(run_if_playing))
+            .with_system(spawn_random_asteroid)
+        )
+        .add_system_set(
+            SystemSet::on_update(GameState::Playing)
+            .with_system(place_wave_asteroids)
+            .with_system(spin_asteroids)
+            .with_system(asteroid_bullet_collision)
+            .with_system(asteroid_player_collision)
+            .with_system(break_asteroids)
+        );
+    }
+}
+
+impl AsteroidSize{
+    fn size(&self) -> (f32, f32){
+        match self{
+            AsteroidSize::Large => (64., 64.),
+            AsteroidSize::Medium => (40., 40.),
+            AsteroidSize::Small => (24., 24.),
+        }
+    }
+
+    fn health(&self) -> f32{
+        match self{
+            AsteroidSize::Large => 4.,
+            AsteroidSize::Medium => 2.,
+            AsteroidSize::Small => 1.,
+        }
+    }
+
+    fn speed(&self) -> f32{
+        match self{
+            AsteroidSize::Large => 0.15,
+            AsteroidSize::Medium => 0.22,
+            AsteroidSize::Small => 0.3,
+        }
+    }
+
+    fn contact_damage(&self) -> f32{
+        match self{
+            AsteroidSize::Large => 2.,
+            AsteroidSize::Medium | AsteroidSize::Small => 1.,
+        }
+    }
+
+    //what it breaks into, small asteroids just crumble
+    fn smaller(&self) -> Option<AsteroidSize>{
+        match self{
+            AsteroidSize::Large => Some(AsteroidSize::Medium),
+            AsteroidSize::Medium => Some(AsteroidSize::Small),
+            AsteroidSize::Small => None,
+        }
+    }
+}
+
+pub fn spawn_asteroid<R: Rng>(commands: &mut Commands, texture: &GameTextures, size: AsteroidSize, position: Vec2, velocity: Vec2, random_generator: &mut R) -> Entity{
+    let (width, height) = size.size();
+
+    commands.spawn_bundle(SpriteBundle{
+        texture: texture.asteroid.clone(),
+        sprite: Sprite{
+            custom_size: Some(Vec2::new(width, height)),
+            ..default()
+        },
+        transform: Transform::from_translation(position.extend(ASTEROID_Z_LAYER)),
+        ..default()
+    })
+    .insert(Asteroid(size))
+    .insert(Spin(random_generator.gen_range(-ASTEROID_MAX_SPIN..ASTEROID_MAX_SPIN)))
+    .insert(SpriteSize::from(size.size()))
+    .insert(Health(size.health()))
+    .insert(Velocity {x: velocity.x, y: velocity.y})
+    .insert(MovableObject {auto_despawn: true})
+    .id()
+}
+
+fn spawn_random_asteroid(mut commands: Commands, texture: Res<GameTextures>, mut random_generator: ResMut<GameRng>){
+    if !random_generator.gen_bool(ASTEROID_SPAWN_CHANCE){
+        return;
+    }
+
+    let size = if random_generator.gen_bool(0.5) { AsteroidSize::Large } else { AsteroidSize::Medium };
+    let position = Vec2::new(random_generator.gen_range(-ARENA_WIDTH / 2. + 40.0..ARENA_WIDTH / 2. - 40.), ARENA_HEIGHT / 2. + 60.);
+    let drift = random_generator.gen_range(-0.05..0.05);
+
+    spawn_asteroid(&mut commands, &texture, size, position, Vec2::new(drift, -size.speed()), &mut random_generator.0);
+}
+
+fn place_wave_asteroids(mut commands: Commands, texture: Res<GameTextures>, wave: Res<Wave>, mut random_generator: ResMut<GameRng>, mut placed_wave: Local<u32>){
+    if *placed_wave == wave.number{
+        return;
+    }
+    *placed_wave = wave.number;
+
+    let placements = WAVE_ASTEROIDS.iter().find(|(number, _)| *number == wave.number);
+    for (x, size) in placements.map_or(&[][..], |(_, placements)| placements).iter(){
+        spawn_asteroid(&mut commands, &texture, *size, Vec2::new(*x, ARENA_HEIGHT / 2. + 60.), Vec2::new(0., -size.speed()), &mut random_generator.0);
+    }
+}
+
+fn spin_asteroids(time: ScaledTime, mut query: Query<(&Spin, &mut Transform)>){
+    for (spin, mut transform) in query.iter_mut(){
+        transform.rotate(Quat::from_rotation_z(spin.0 * time.delta_seconds()));
+    }
+}
+
+//asteroids stop bullets from both sides, and take damage from them
+fn asteroid_bullet_collision(mut commands: Commands, mut bullet_pool: ResMut<BulletPool>, mut explosions: EventWriter<ExplosionToSpawn>,
+    bullet_query: Query<(Entity, &Transform, &SpriteSize), With<Bullet>>,
+    mut asteroid_query: Query<(&Transform, &SpriteSize, &mut Health), With<Asteroid>>){
+
+    for (bullet_entity, bullet_transform, bullet_size) in bullet_query.iter(){
+        for (asteroid_transform, asteroid_size, mut asteroid_health) in asteroid_query.iter_mut(){
+            if asteroid_health.0 <= 0.{
+                continue;
+            }
+
+            let collision = collide(
+                bullet_transform.translation, bullet_size.0 * bullet_transform.scale.truncate(),
+                asteroid_transform.translation, asteroid_size.0);
+
+            if collision.is_some(){
+                asteroid_health.0 -= 1.;
+                explosions.send(ExplosionToSpawn(bullet_transform.translation, EffectKind::BulletHit));
+                bullet_pool.release(&mut commands, bullet_entity);
+                break;
+            }
+        }
+    }
+}
+
+type RammablePlayer = (With<Player>, Without<Invulnerable>, Without<Asteroid>);
+
+fn asteroid_player_collision(mut commands: Commands, mut player_hits: EventWriter<PlayerHit>, mut player_deaths: EventWriter<PlayerDied>,
+    mut player_query: Query<ShieldedPlayer, RammablePlayer>,
+    mut asteroid_query: Query<(&Asteroid, &Transform, &SpriteSize, &mut Health)>){
+
+    for (player_entity, player_transform, player_size, mut player_health, mut shield) in player_query.iter_mut(){
+        for (asteroid, asteroid_transform, asteroid_size, mut asteroid_health) in asteroid_query.iter_mut(){
+            if asteroid_health.0 <= 0.{
+                continue;
+            }
+
+            let collision = collide(
+                player_transform.translation, player_size.0 * player_transform.scale.truncate(),
+                asteroid_transform.translation, asteroid_size.0);
+
+            if collision.is_none(){
+                continue;
+            }
+
+            //the asteroid breaks on impact
+            asteroid_health.0 = 0.;
+
+            let damage = asteroid.0.contact_damage();
+            let depleted = player_health.take_damage(damage, shield.as_deref_mut());
+            player_hits.send(PlayerHit { damage, source: Some("asteroid") });
+
+            if depleted{
+                commands.entity(player_entity).despawn_recursive();
+                player_deaths.send(PlayerDied { position: player_transform.translation });
+                break;
+            }
+        }
+    }
+}
+
+//destroyed asteroids split into two smaller ones flying apart
+fn break_asteroids(mut commands: Commands, texture: Res<GameTextures>, mut random_generator: ResMut<GameRng>, mut explosions: EventWriter<ExplosionToSpawn>, query: Query<(Entity, &Asteroid, &Transform, &Velocity, &Health)>){
+    for (entity, asteroid, transform, velocity, health) in query.iter(){
+        if health.0 > 0.{
+            continue;
+        }
+
+        commands.entity(entity).despawn();
+        explosions.send(ExplosionToSpawn(transform.translation, EffectKind::BulletHit));
+
+        if let Some(smaller) = asteroid.0.smaller(){
+            let position = transform.translation.truncate();
+            for side in [-1., 1.]{
+                let velocity = Vec2::new(velocity.x + side * ASTEROID_SPLIT_SPEED, -smaller.speed());
+                spawn_asteroid(&mut commands, &texture, smaller, position + Vec2::new(side * smaller.size().0 / 2., 0.), velocity, &mut random_generator.0);
+            }
+        }
+    }
+}
@@ -0,0 +1,387 @@
+use bevy::prelude::*;
+#[cfg(feature = "audio")]
+use bevy::audio::{Audio, AudioSink, AudioSource, PlaybackSettings};
+#[cfg(feature = "audio")]
+use bevy::utils::HashMap;
+use bevy::utils::HashSet;
+use crate::components::{BulletFired, GameState};
+#[cfg(feature = "audio")]
+use crate::settings::GameSettings;
+
+const MUSIC_CROSSFADE_SECONDS: f32 = 1.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AudioBackend{
+    #[cfg(feature = "audio")]
+    Bevy,
+    //records requests instead of playing them, for headless runs
+    Null,
+}
+
+pub struct GameAudioPlugin{
+    pub backend: AudioBackend,
+}
+
+impl Plugin for GameAudioPlugin{
+    fn build(&self, app: &mut App) {
+        app
+        .init_resource::<SoundQueue>()
+        .init_resource::<MusicState>()
+        .add_system(update_music_state)
+        .add_system(play_shot_sounds);
+
+        match self.backend{
+            #[cfg(feature = "audio")]
+            AudioBackend::Bevy => {
+                app
+                .init_resource::<MusicSinks>()
+                .add_startup_system(load_audio_library)
+                .add_system_to_stage(CoreStage::PostUpdate, play_sounds)
+                .add_system_to_stage(CoreStage::PostUpdate, play_music);
+            },
+            AudioBackend::Null => {
+                app
+                .init_resource::<NullAudio>()
+                .add_system_to_stage(CoreStage::PostUpdate, record_null_audio);
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SoundEffect{
+    PlayerShot,
+    EnemyShot,
+    Hit,
+    Explosion,
+    Pickup,
+}
+
+impl SoundEffect{
+    #[cfg(any(feature = "audio", test))]
+    pub const ALL: [SoundEffect; 5] = [
+        SoundEffect::PlayerShot,
+        SoundEffect::EnemyShot,
+        SoundEffect::Hit,
+        SoundEffect::Explosion,
+        SoundEffect::Pickup,
+    ];
+
+    #[cfg(feature = "audio")]
+    fn path(&self) -> &'static str{
+        match self{
+            SoundEffect::PlayerShot => "sounds/player_shot.wav",
+            SoundEffect::EnemyShot => "sounds/enemy_shot.wav",
+            SoundEffect::Hit => "sounds/hit.wav",
+            SoundEffect::Explosion => "sounds/explosion.wav",
+            SoundEffect::Pickup => "sounds/pickup.wav",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MusicTrack{
+    Menu,
+    Gameplay,
+    GameOver,
+}
+
+impl MusicTrack{
+    #[cfg(any(feature = "audio", test))]
+    pub const ALL: [MusicTrack; 3] = [
+        MusicTrack::Menu,
+        MusicTrack::Gameplay,
+        MusicTrack::GameOver,
+    ];
+
+    pub fn for_state(state: &GameState) -> Option<MusicTrack>{
+        match state{
+            GameState::MainMenu | GameState::Options => Some(MusicTrack::Menu),
+            GameState::Playing | GameState::Paused => Some(MusicTrack::Gameplay),
+            GameState::GameOver => Some(MusicTrack::GameOver),
+            GameState::Loading | GameState::AssetError => None,
+        }
+    }
+
+    #[cfg(feature = "audio")]
+    fn path(&self) -> &'static str{
+        match self{
+            MusicTrack::Menu => "music/menu.wav",
+            MusicTrack::Gameplay => "music/gameplay.wav",
+            MusicTrack::GameOver => "music/game_over.wav",
+        }
+    }
+}
+
+//Sounds requested this frame. A set, so twenty explosions in one frame play one sound.
+#[derive(Default)]
+pub struct SoundQueue(HashSet<SoundEffect>);
+
+impl SoundQueue{
+    pub fn play(&mut self, sound: SoundEffect){
+        self.0.insert(sound);
+    }
+
+    pub fn drain(&mut self) -> impl Iterator<Item = SoundEffect> + '_{
+        self.0.drain()
+    }
+}
+
+//Crossfade between the track of the previous state and the current one, `fade` goes from 0 to 1
+#[derive(Default)]
+pub struct MusicState{
+    pub current: Option<MusicTrack>,
+    pub previous: Option<MusicTrack>,
+    pub fade: f32,
+}
+
+impl MusicState{
+    pub fn request(&mut self, track: Option<MusicTrack>){
+        if track == self.current{
+            return;
+        }
+        self.previous = self.current;
+        self.current = track;
+        self.fade = 0.;
+    }
+
+    pub fn advance(&mut self, delta: f32){
+        self.fade = (self.fade + delta / MUSIC_CROSSFADE_SECONDS).min(1.);
+        if self.fade >= 1.{
+            self.previous = None;
+        }
+    }
+
+    #[cfg(any(feature = "audio", test))]
+    pub fn volume_of(&self, track: MusicTrack) -> f32{
+        if self.current == Some(track){
+            self.fade
+        }else if self.previous == Some(track){
+            1. - self.fade
+        }else{
+            0.
+        }
+    }
+}
+
+//only the last frame is kept, so long headless runs don't grow it without bound
+#[derive(Default)]
+pub struct NullAudio{
+    pub played: Vec<SoundEffect>,
+    pub total_played: usize,
+    pub music: Option<MusicTrack>,
+}
+
+#[cfg(feature = "audio")]
+struct AudioLibrary{
+    sounds: HashMap<SoundEffect, Handle<AudioSource>>,
+    music: HashMap<MusicTrack, Handle<AudioSource>>,
+}
+
+#[cfg(feature = "audio")]
+#[derive(Default)]
+struct MusicSinks(HashMap<MusicTrack, Handle<AudioSink>>);
+
+#[cfg(feature = "audio")]
+fn load_audio_library(mut commands: Commands, asset_server: Res<AssetServer>){
+    let sounds = SoundEffect::ALL.iter()
+        .map(|sound| (*sound, asset_server.load(sound.path())))
+        .collect();
+
+    let music = MusicTrack::ALL.iter()
+        .map(|track| (*track, asset_server.load(track.path())))
+        .collect();
+
+    commands.insert_resource(AudioLibrary {sounds, music});
+}
+
+fn play_shot_sounds(mut bullets_fired: EventReader<BulletFired>, mut sounds: ResMut<SoundQueue>){
+    for bullet in bullets_fired.iter(){
+        sounds.play(if bullet.from_player { SoundEffect::PlayerShot } else { SoundEffect::EnemyShot });
+    }
+}
+
+fn update_music_state(time: Res<Time>, state: Res<State<GameState>>, mut music: ResMut<MusicState>){
+    music.request(MusicTrack::for_state(state.current()));
+    music.advance(time.delta_seconds());
+}
+
+#[cfg(feature = "audio")]
+fn play_sounds(audio: Res<Audio>, library: Res<AudioLibrary>, settings: Res<GameSettings>, mut queue: ResMut<SoundQueue>){
+    let volume = settings.effective_sfx_volume();
+
+    for sound in queue.drain(){
+        if let Some(source) = library.sounds.get(&sound){
+            audio.play_with_settings(source.clone(), PlaybackSettings::ONCE.with_volume(volume));
+        }
+    }
+}
+
+#[cfg(feature = "audio")]
+fn play_music(audio: Res<Audio>, library: Res<AudioLibrary>, settings: Res<GameSettings>, music: Res<MusicState>, audio_sinks: Res<Assets<AudioSink>>, mut sinks: ResMut<MusicSinks>){
+
+    //start tracks that became audible
+    for track in [music.current, music.previous].into_iter().flatten(){
+        if sinks.0.contains_key(&track){
+            continue;
+        }
+        if let Some(source) = library.music.get(&track){
+            let sink = audio.play_with_settings(source.clone(), PlaybackSettings::LOOP.with_volume(0.));
+            sinks.0.insert(track, audio_sinks.get_handle(sink));
+        }
+    }
+
+    //fade the playing ones, stop the ones that faded out
+    let volume = settings.effective_music_volume();
+    sinks.0.retain(|track, handle| {
+        let track_volume = music.volume_of(*track);
+        match audio_sinks.get(&*handle){
+            Some(sink) if track_volume > 0. || music.current == Some(*track) => {
+                sink.set_volume(track_volume * volume);
+                true
+            },
+            Some(sink) => {
+                sink.pause();
+                false
+            },
+            None => true,
+        }
+    });
+}
+
+fn record_null_audio(music: Res<MusicState>, mut queue: ResMut<SoundQueue>, mut null_audio: ResMut<NullAudio>){
+    null_audio.played.clear();
+    null_audio.played.extend(queue.drain());
+    null_audio.total_played += null_audio.played.len();
+    null_audio.music = music.current;
+}
+
+#[cfg(test)]
+mod tests{
+    use bevy::ecs::event::Events;
+    use super::*;
+
+    fn headless_app(state: GameState) -> App{
+        let mut app = App::new();
+        app
+        .add_plugins(MinimalPlugins)
+        .add_state(state)
+        .add_event::<BulletFired>()
+        .add_plugin(GameAudioPlugin { backend: AudioBackend::Null });
+        app
+    }
+
+    #[test]
+    fn identical_sounds_play_once_per_frame(){
+        let mut app = headless_app(GameState::Playing);
+
+        for _ in 0..20{
+            app.world.resource_mut::<SoundQueue>().play(SoundEffect::Explosion);
+        }
+        app.world.resource_mut::<SoundQueue>().play(SoundEffect::Hit);
+        app.update();
+
+        let mut played = app.world.resource::<NullAudio>().played.clone();
+        played.sort_by_key(|sound| SoundEffect::ALL.iter().position(|other| other == sound));
+        assert_eq!(played, vec![SoundEffect::Hit, SoundEffect::Explosion]);
+
+        //the next frame is a new frame
+        app.world.resource_mut::<SoundQueue>().play(SoundEffect::Explosion);
+        app.update();
+        assert_eq!(app.world.resource::<NullAudio>().played, vec![SoundEffect::Explosion]);
+        assert_eq!(app.world.resource::<NullAudio>().total_played, 3);
+
+        //a silent frame leaves nothing behind
+        app.update();
+        assert!(app.world.resource::<NullAudio>().played.is_empty());
+        assert_eq!(app.world.resource::<NullAudio>().total_played, 3);
+    }
+
+    #[test]
+    fn shots_are_queued_from_events(){
+        let mut app = headless_app(GameState::Playing);
+
+        for _ in 0..5{
+            app.world.resource_mut::<Events<BulletFired>>().send(BulletFired { from_player: false });
+        }
+        app.update();
+
+        assert_eq!(app.world.resource::<NullAudio>().played, vec![SoundEffect::EnemyShot]);
+    }
+
+    #[test]
+    fn music_follows_the_state(){
+        let mut app = headless_app(GameState::Playing);
+        app.update();
+        assert_eq!(app.world.resource::<NullAudio>().music, Some(MusicTrack::Gameplay));
+
+        //the transition is applied during the frame, the music picks it up on the next one
+        let _ = app.world.resource_mut::<State<GameState>>().set(GameState::GameOver);
+        app.update();
+        app.update();
+        assert_eq!(app.world.resource::<NullAudio>().music, Some(MusicTrack::GameOver));
+
+        let _ = app.world.resource_mut::<State<GameState>>().set(GameState::MainMenu);
+        app.update();
+        app.update();
+        assert_eq!(app.world.resource::<NullAudio>().music, Some(MusicTrack::Menu));
+    }
+
+    #[test]
+    fn every_state_outside_loading_has_a_track(){
+        for track in MusicTrack::ALL{
+            let states = [GameState::MainMenu, GameState::Options, GameState::Playing, GameState::Paused, GameState::GameOver];
+            assert!(states.iter().any(|state| MusicTrack::for_state(state) == Some(track)));
+        }
+        assert_eq!(MusicTrack::for_state(&GameState::Loading), None);
+    }
+
+    #[test]
+    fn crossfade_reaches_the_target_track(){
+        let mut music = MusicState::default();
+        music.request(Some(MusicTrack::Gameplay));
+        assert_eq!(music.volume_of(MusicTrack::Gameplay), 0.);
+
+        music.advance(MUSIC_CROSSFADE_SECONDS / 2.);
+        assert!((music.volume_of(MusicTrack::Gameplay) - 0.5).abs() < 1e-4);
+
+        music.advance(MUSIC_CROSSFADE_SECONDS);
+        assert_eq!(music.volume_of(MusicTrack::Gameplay), 1.);
+        assert_eq!(music.current, Some(MusicTrack::Gameplay));
+        assert_eq!(music.previous, None);
+    }
+
+    #[test]
+    fn crossfade_between_two_tracks(){
+        let mut music = MusicState::default();
+        music.request(Some(MusicTrack::Menu));
+        music.advance(MUSIC_CROSSFADE_SECONDS);
+
+        music.request(Some(MusicTrack::Gameplay));
+        assert_eq!(music.previous, Some(MusicTrack::Menu));
+        music.advance(MUSIC_CROSSFADE_SECONDS / 4.);
+        assert!((music.volume_of(MusicTrack::Menu) - 0.75).abs() < 1e-4);
+        assert!((music.volume_of(MusicTrack::Gameplay) - 0.25).abs() < 1e-4);
+        assert_eq!(music.volume_of(MusicTrack::GameOver), 0.);
+
+        music.advance(MUSIC_CROSSFADE_SECONDS);
+        assert_eq!(music.volume_of(MusicTrack::Menu), 0.);
+        assert_eq!(music.volume_of(MusicTrack::Gameplay), 1.);
+        assert_eq!(music.previous, None);
+    }
+
+    #[test]
+    fn crossfade_fades_the_previous_track_out(){
+        let mut music = MusicState::default();
+        music.request(Some(MusicTrack::Gameplay));
+        music.advance(MUSIC_CROSSFADE_SECONDS);
+
+        music.request(None);
+        music.advance(MUSIC_CROSSFADE_SECONDS / 4.);
+        assert!((music.volume_of(MusicTrack::Gameplay) - 0.75).abs() < 1e-4);
+
+        music.advance(MUSIC_CROSSFADE_SECONDS);
+        assert_eq!(music.volume_of(MusicTrack::Gameplay), 0.);
+        assert_eq!(music.previous, None);
+    }
+}
@@ -0,0 +1,158 @@
+use bevy::prelude::*;
+use bevy::ecs::system::SystemParam;
+use crate::components::{Autopilot, Bullet, ComingFromEnemy, DemoText, Enemy, GameClock, GameState, Player, PlayerIntent, PlayerState};
+use crate::ARENA_WIDTH;
+use crate::utils::GameSystem;
+
+//how far ahead and how wide the autopilot looks for bullets coming down on it
+const DODGE_LOOKAHEAD: f32 = 250.;
+const DODGE_WIDTH: f32 = 40.;
+const AIM_TOLERANCE: f32 = 12.;
+const EDGE_MARGIN: f32 = 60.;
+//bombs once this many enemy bullets are this close
+const BOMB_THREAT_RADIUS: f32 = 150.;
+const BOMB_THREAT_COUNT: usize = 6;
+//a quick player taps fire about seven times a second
+const FIRE_INTERVAL: f32 = 0.15;
+
+const ATTRACT_IDLE_SECONDS: f32 = 20.;
+const ATTRACT_DEMO_SECONDS: f32 = 60.;
+const DEMO_FONT: &str = "fonts/FiraSans-Regular.ttf";
+const DEMO_FONT_SIZE: f32 = 28.;
+const DEMO_TEXT_COLOR: Color = Color::rgb(1.0, 0.9, 0.3);
+
+pub struct AutopilotPlugin;
+
+impl Plugin for AutopilotPlugin{
+    fn build(&self, app: &mut App) {
+        app
+        .init_resource::<Autopilot>()
+        .add_system_set(SystemSet::on_update(GameState::MainMenu).with_system(start_attract_mode))
+        .add_system_set(SystemSet::on_enter(GameState::Playing).with_system(spawn_demo_text))
+        .add_system_set(SystemSet::on_exit(GameState::Playing).with_system(despawn_demo_text))
+        .add_system_set(
+            SystemSet::on_update(GameState::Playing)
+            .with_system(drive_autopilot.label(GameSystem::Input))
+            .with_system(end_attract_mode)
+        )
+        .add_system_set(SystemSet::on_update(GameState::GameOver).with_system(end_attract_mode));
+    }
+}
+
+//what the autopilot looks at
+#[derive(SystemParam)]
+struct Surroundings<'w, 's>{
+    player: Query<'w, 's, &'static Transform, With<Player>>,
+    enemy_bullets: Query<'w, 's, &'static Transform, (With<Bullet>, With<ComingFromEnemy>)>,
+    enemies: Query<'w, 's, &'static Transform, With<Enemy>>,
+}
+
+//dodges the nearest bullet coming down on it, otherwise lines up under the nearest enemy and fires
+fn drive_autopilot(autopilot: Res<Autopilot>, player_state: Res<PlayerState>, clock: Res<GameClock>, mut intent: ResMut<PlayerIntent>, mut reload: Local<f32>,
+    surroundings: Surroundings){
+
+    if !autopilot.enabled{
+        return;
+    }
+
+    *intent = PlayerIntent::default();
+    *reload = (*reload - clock.delta_seconds()).max(0.);
+
+    let position = match surroundings.player.get_single(){
+        Ok(player) => player.translation.truncate(),
+        Err(_) => return,
+    };
+    let nearest = |a: &Vec2, b: &Vec2| a.distance_squared(position).total_cmp(&b.distance_squared(position));
+
+    let bullets: Vec<Vec2> = surroundings.enemy_bullets.iter().map(|bullet| bullet.translation.truncate()).collect();
+    let close_bullets = bullets.iter().filter(|bullet| bullet.distance(position) < BOMB_THREAT_RADIUS).count();
+    intent.bomb = close_bullets >= BOMB_THREAT_COUNT && player_state.bombs > 0;
+
+    let threat = bullets.iter()
+        .filter(|bullet| bullet.y > position.y && bullet.y - position.y < DODGE_LOOKAHEAD && (bullet.x - position.x).abs() < DODGE_WIDTH)
+        .min_by(|a, b| nearest(a, b));
+    let target = surroundings.enemies.iter()
+        .map(|enemy| enemy.translation.truncate())
+        .filter(|enemy| enemy.y > position.y)
+        .min_by(nearest);
+
+    if let Some(bullet) = threat{
+        //away from the bullet, unless that runs into the arena edge
+        let mut away = if bullet.x > position.x { -1. } else { 1. };
+        if (position.x + away * EDGE_MARGIN).abs() > ARENA_WIDTH / 2. - EDGE_MARGIN{
+            away = -away;
+        }
+        intent.move_x = away;
+    }else if let Some(enemy) = target{
+        let offset = enemy.x - position.x;
+        if offset.abs() > AIM_TOLERANCE{
+            intent.move_x = offset.signum();
+        }else if *reload <= 0.{
+            //the keyboard fires on presses, so the autopilot taps at a pace a player could keep up
+            intent.fire = true;
+            *reload = FIRE_INTERVAL;
+        }
+    }
+}
+
+//the title screen starts a demo game once nobody has touched the keyboard for a while
+fn start_attract_mode(time: Res<Time>, input: Res<Input<KeyCode>>, mut idle_seconds: Local<f32>, mut autopilot: ResMut<Autopilot>, mut state: ResMut<State<GameState>>){
+    if input.get_just_pressed().next().is_some(){
+        *idle_seconds = 0.;
+        return;
+    }
+
+    *idle_seconds += time.delta_seconds();
+    if *idle_seconds >= ATTRACT_IDLE_SECONDS{
+        *idle_seconds = 0.;
+        *autopilot = Autopilot { enabled: true, attract_mode: true, ..default() };
+        let _ = state.set(GameState::Playing);
+    }
+}
+
+//any key, a game over or the end of the demo goes back to the title screen, the key is used up so the menu does not see it
+fn end_attract_mode(time: Res<Time>, mut input: ResMut<Input<KeyCode>>, mut autopilot: ResMut<Autopilot>, mut state: ResMut<State<GameState>>){
+    if !autopilot.attract_mode{
+        return;
+    }
+
+    autopilot.demo_seconds += time.delta_seconds();
+    let pressed: Vec<KeyCode> = input.get_just_pressed().copied().collect();
+    let game_over = *state.current() == GameState::GameOver;
+    if pressed.is_empty() && !game_over && autopilot.demo_seconds < ATTRACT_DEMO_SECONDS{
+        return;
+    }
+
+    for key in pressed{
+        input.reset(key);
+    }
+    *autopilot = Autopilot::default();
+    let _ = state.set(GameState::MainMenu);
+}
+
+fn spawn_demo_text(mut commands: Commands, asset_server: Res<AssetServer>, autopilot: Res<Autopilot>){
+    if !autopilot.attract_mode{
+        return;
+    }
+
+    commands.spawn_bundle(TextBundle{
+        style: Style{
+            position_type: PositionType::Absolute,
+            position: Rect { bottom: Val::Percent(40.), left: Val::Percent(25.), ..default() },
+            ..default()
+        },
+        text: Text::with_section("DEMO - press any key", TextStyle{
+            font: asset_server.load(DEMO_FONT),
+            font_size: DEMO_FONT_SIZE,
+            color: DEMO_TEXT_COLOR,
+        }, default()),
+        ..default()
+    })
+    .insert(DemoText);
+}
+
+fn despawn_demo_text(mut commands: Commands, query: Query<Entity, With<DemoText>>){
+    for entity in query.iter(){
+        commands.entity(entity).despawn_recursive();
+    }
+}
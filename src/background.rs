@@ -0,0 +1,81 @@
+use bevy::prelude::*;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use crate::components::{GameSeed, ParallaxLayer, StarfieldSettings, TimeScale};
+use crate::{ARENA_HEIGHT, ARENA_WIDTH, GAME_SPEED, TIME_STEP};
+
+const BACKGROUND_Z_LAYER: f32 = 0.;
+const STAR_SPEED_MIN: f32 = 0.02;
+const STAR_SPEED_MAX: f32 = 0.12;
+const NEBULA_SPEED: f32 = 0.01;
+
+pub struct BackgroundPlugin;
+
+impl Plugin for BackgroundPlugin{
+    fn build(&self, app: &mut App) {
+        app
+        .init_resource::<StarfieldSettings>()
+        .add_startup_system(spawn_starfield)
+        .add_system(scroll_background);
+    }
+}
+
+fn spawn_starfield(mut commands: Commands, asset_server: Res<AssetServer>, settings: Res<StarfieldSettings>, seed: Res<GameSeed>){
+
+    let mut random_generator = StdRng::seed_from_u64(seed.0);
+    let layers = settings.layers.max(1);
+
+    for layer in 0..layers{
+        //far layers are slower, smaller and dimmer
+        let depth = (layer + 1) as f32 / layers as f32;
+        let speed = STAR_SPEED_MIN + (STAR_SPEED_MAX - STAR_SPEED_MIN) * depth;
+        let size = 1. + depth * 2.;
+        let brightness = 0.3 + depth * 0.7;
+
+        for _ in 0..settings.stars_per_layer{
+            let position_x = random_generator.gen_range((-ARENA_WIDTH/2.)..(ARENA_WIDTH/2.));
+            let position_y = random_generator.gen_range((-ARENA_HEIGHT/2.)..(ARENA_HEIGHT/2.));
+            let twinkle = random_generator.gen_range(0.8..1.0);
+
+            commands.spawn_bundle(SpriteBundle{
+                sprite: Sprite{
+                    color: Color::rgba(brightness, brightness, brightness * 1.1, twinkle),
+                    custom_size: Some(Vec2::splat(size)),
+                    ..default()
+                },
+                transform: Transform::from_xyz(position_x, position_y, BACKGROUND_Z_LAYER + depth * 0.5),
+                ..default()
+            })
+            .insert(ParallaxLayer {speed, wrap_height: ARENA_HEIGHT});
+        }
+    }
+
+    if let Some(nebula_image) = &settings.nebula_image{
+        let texture: Handle<Image> = asset_server.load(nebula_image.as_str());
+
+        //two stacked tiles so one is always covering the screen while the other wraps
+        for tile in 0..2{
+            commands.spawn_bundle(SpriteBundle{
+                texture: texture.clone(),
+                sprite: Sprite{
+                    custom_size: Some(Vec2::new(ARENA_WIDTH, ARENA_HEIGHT)),
+                    ..default()
+                },
+                transform: Transform::from_xyz(0., tile as f32 * ARENA_HEIGHT, BACKGROUND_Z_LAYER),
+                ..default()
+            })
+            .insert(ParallaxLayer {speed: NEBULA_SPEED, wrap_height: ARENA_HEIGHT * 2.});
+        }
+    }
+}
+
+fn scroll_background(time_scale: Res<TimeScale>, mut query: Query<(&ParallaxLayer, &mut Transform)>){
+
+    for (layer, mut transform) in query.iter_mut(){
+        let translation = &mut transform.translation;
+        translation.y -= layer.speed * TIME_STEP * GAME_SPEED * time_scale.0;
+
+        if translation.y < -layer.wrap_height / 2.{
+            translation.y += layer.wrap_height;
+        }
+    }
+}
@@ -0,0 +1,309 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use bevy::prelude::*;
+use bevy::core::DefaultTaskPoolOptions;
+use bevy::log::LogPlugin;
+use bevy::render::settings::WgpuSettings;
+use bevy::utils::HashMap;
+use space_shooter::*;
+
+//Plays headless games with the autopilot and writes one summary line per run, for balancing.
+//Each frame advances the game clock by one fixed step, so games run as fast as the machine allows, in parallel with the others.
+const USAGE: &str = "usage: balance_sim [--games N] [--difficulty easy,normal,hard] [--seed S] [--max-seconds T] [--jobs J] [--format csv|json] [--out FILE]";
+
+const DEFAULT_GAMES: u32 = 100;
+const DEFAULT_MAX_SECONDS: f32 = 300.;
+const DEFAULT_JOBS: usize = 16;
+
+//damage sources besides the enemy archetypes
+const OTHER_DAMAGE_SOURCES: &[&str] = &["asteroid", "unknown"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OutputFormat{
+    Csv,
+    Json,
+}
+
+struct SimOptions{
+    games: u32,
+    difficulties: Vec<Difficulty>,
+    seed: u64,
+    max_seconds: f32,
+    jobs: usize,
+    format: OutputFormat,
+    out: Option<String>,
+}
+
+impl Default for SimOptions{
+    fn default() -> Self {
+        Self {
+            games: DEFAULT_GAMES,
+            difficulties: Difficulty::ALL.to_vec(),
+            seed: 0,
+            max_seconds: DEFAULT_MAX_SECONDS,
+            jobs: DEFAULT_JOBS,
+            format: OutputFormat::Csv,
+            out: None,
+        }
+    }
+}
+
+impl SimOptions{
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String>{
+        let mut options = Self::default();
+
+        while let Some(flag) = args.next(){
+            if flag == "--help"{
+                println!("{}", USAGE);
+                std::process::exit(0);
+            }
+
+            let value = args.next().ok_or_else(|| format!("missing value for {}", flag))?;
+            let invalid = || format!("invalid value for {}: {}", flag, value);
+
+            match flag.as_str(){
+                "--games" => options.games = value.parse().map_err(|_| invalid())?,
+                "--seed" => options.seed = value.parse().map_err(|_| invalid())?,
+                "--max-seconds" => options.max_seconds = value.parse().map_err(|_| invalid())?,
+                "--jobs" => options.jobs = value.parse::<usize>().map_err(|_| invalid())?.max(1),
+                "--out" => options.out = Some(value),
+                "--format" => options.format = match value.as_str(){
+                    "csv" => OutputFormat::Csv,
+                    "json" => OutputFormat::Json,
+                    _ => return Err(invalid()),
+                },
+                "--difficulty" => {
+                    options.difficulties = value.split(',')
+                        .map(|name| Difficulty::ALL.iter().copied().find(|difficulty| difficulty.name().eq_ignore_ascii_case(name.trim())))
+                        .collect::<Option<_>>()
+                        .ok_or_else(invalid)?;
+                },
+                _ => return Err(format!("unknown option {}", flag)),
+            }
+        }
+
+        Ok(options)
+    }
+}
+
+//Everything measured during one game, filled in by record_run
+#[derive(Debug, Clone)]
+struct RunSummary{
+    difficulty: Difficulty,
+    seed: u64,
+    survival_seconds: f32,
+    score: u32,
+    wave: u32,
+    deaths: u32,
+    damage_taken: HashMap<&'static str, f32>,
+    game_over: bool,
+}
+
+impl RunSummary{
+    fn new(difficulty: Difficulty, seed: u64) -> Self{
+        Self { difficulty, seed, survival_seconds: 0., score: 0, wave: 1, deaths: 0, damage_taken: HashMap::default(), game_over: false }
+    }
+
+    fn outcome(&self) -> &'static str{
+        if self.game_over { "game_over" } else { "timeout" }
+    }
+
+    fn damage_from(&self, source: &str) -> f32{
+        self.damage_taken.get(source).copied().unwrap_or(0.)
+    }
+}
+
+//one column per source, in this order
+fn damage_sources() -> Vec<&'static str>{
+    ENEMY_ARCHETYPES.iter().map(|archetype| archetype.name).chain(OTHER_DAMAGE_SOURCES.iter().copied()).collect()
+}
+
+fn main(){
+    let options = match SimOptions::parse(std::env::args().skip(1)){
+        Ok(options) => options,
+        Err(error) => {
+            eprintln!("{}\n{}", error, USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    let queue: VecDeque<(Difficulty, u64)> = options.difficulties.iter()
+        .flat_map(|difficulty| (0..options.games as u64).map(move |game| (*difficulty, options.seed + game)))
+        .collect();
+    let total = queue.len();
+    let queue = Arc::new(Mutex::new(queue));
+    let results = Arc::new(Mutex::new(Vec::with_capacity(total)));
+
+    let workers: Vec<_> = (0..options.jobs.min(total.max(1))).map(|_| {
+        let queue = queue.clone();
+        let results = results.clone();
+        let max_seconds = options.max_seconds;
+
+        thread::spawn(move || loop {
+            let next = queue.lock().unwrap().pop_front();
+            let (difficulty, seed) = match next{
+                Some(job) => job,
+                None => break,
+            };
+
+            match run_game(difficulty, seed, max_seconds){
+                Ok(summary) => {
+                    let mut results = results.lock().unwrap();
+                    results.push(summary);
+                    eprintln!("[{}/{}] {} seed {}: {}", results.len(), total, difficulty.name(), seed, results.last().unwrap().outcome());
+                },
+                Err(error) => eprintln!("{} seed {} failed: {}", difficulty.name(), seed, error),
+            }
+        })
+    }).collect();
+
+    for worker in workers{
+        worker.join().expect("simulation thread panicked");
+    }
+
+    let mut results = std::mem::take(&mut *results.lock().unwrap());
+    results.sort_by_key(|summary| (Difficulty::ALL.iter().position(|difficulty| *difficulty == summary.difficulty), summary.seed));
+
+    let output = match options.format{
+        OutputFormat::Csv => to_csv(&results),
+        OutputFormat::Json => to_json(&results),
+    };
+
+    match &options.out{
+        Some(path) => {
+            if let Err(error) = fs::write(path, output){
+                eprintln!("failed to write {}: {}", path, error);
+                std::process::exit(1);
+            }
+        },
+        None => print!("{}", output),
+    }
+}
+
+fn run_game(difficulty: Difficulty, seed: u64, max_seconds: f32) -> Result<RunSummary, String>{
+    let mut app = App::new();
+    app
+    .insert_resource(WgpuSettings { backends: None, ..default() })
+    .insert_resource(DefaultTaskPoolOptions::with_num_threads(1))
+    //the log subscriber is global, it can only be installed by one app per process
+    .add_plugins_with(DefaultPlugins, |group| {
+        group.disable::<LogPlugin>();
+        #[cfg(feature = "render")]
+        group.disable::<bevy::winit::WinitPlugin>().disable::<bevy::gilrs::GilrsPlugin>();
+        #[cfg(feature = "audio")]
+        group.disable::<bevy::audio::AudioPlugin>();
+        group
+    })
+    .add_plugin(SpaceShooterPlugin { audio: AudioBackend::Null, seed })
+    .insert_resource(Autopilot { enabled: true, ..default() })
+    .insert_resource(GameClock::fixed(TIME_STEP))
+    .insert_resource(RunSummary::new(difficulty, seed))
+    .add_system_set(SystemSet::on_update(GameState::MainMenu).with_system(start_run))
+    .add_system(record_run);
+
+    //changed before the first frame, so the game does not save it to the settings file
+    let mut settings = app.world.resource_mut::<GameSettings>();
+    settings.difficulty = difficulty;
+    settings.adaptive_difficulty = false;
+
+    loop{
+        app.update();
+
+        if *app.world.resource::<State<GameState>>().current() == GameState::AssetError{
+            return Err("assets failed to load".to_string());
+        }
+
+        let summary = app.world.resource::<RunSummary>();
+        if summary.game_over || summary.survival_seconds >= max_seconds{
+            return Ok(summary.clone());
+        }
+    }
+}
+
+fn start_run(mut state: ResMut<State<GameState>>){
+    let _ = state.set(GameState::Playing);
+}
+
+//score and wave are reset by a game over, so they are counted from the events instead of read back
+fn record_run(clock: Res<GameClock>, state: Res<State<GameState>>, mut summary: ResMut<RunSummary>,
+    mut kills: EventReader<EnemyKilled>, mut hits: EventReader<PlayerHit>, mut deaths: EventReader<PlayerDied>, mut waves_cleared: EventReader<WaveCleared>){
+
+    if summary.game_over{
+        return;
+    }
+
+    if *state.current() == GameState::Playing{
+        summary.survival_seconds += clock.delta_seconds();
+    }
+
+    summary.score += kills.iter().count() as u32;
+    summary.wave += waves_cleared.iter().count() as u32;
+    for hit in hits.iter(){
+        *summary.damage_taken.entry(hit.source.unwrap_or("unknown")).or_insert(0.) += hit.damage;
+    }
+
+    //same frame deaths count once, like they do for the player's lives
+    if deaths.iter().count() > 0{
+        summary.deaths += 1;
+    }
+
+    //the game decides when the lives are used up
+    summary.game_over = *state.current() == GameState::GameOver;
+}
+
+fn to_csv(results: &[RunSummary]) -> String{
+    let mut csv = String::from("difficulty,seed,outcome,survival_seconds,score,wave,deaths");
+    for source in damage_sources(){
+        csv += &format!(",damage_{}", source);
+    }
+    csv += "\n";
+
+    for summary in results{
+        csv += &format!(
+            "{},{},{},{:.2},{},{},{}",
+            summary.difficulty.name(), summary.seed, summary.outcome(), summary.survival_seconds, summary.score, summary.wave, summary.deaths
+        );
+        for source in damage_sources(){
+            csv += &format!(",{:.2}", summary.damage_from(source));
+        }
+        csv += "\n";
+    }
+
+    csv
+}
+
+fn damage_json(damage_from: impl Fn(&str) -> f32) -> String{
+    let fields: Vec<String> = damage_sources().into_iter()
+        .map(|source| format!("\"{}\": {:.2}", source, damage_from(source)))
+        .collect();
+    format!("{{{}}}", fields.join(", "))
+}
+
+//every run, plus the averages per difficulty
+fn to_json(results: &[RunSummary]) -> String{
+    let runs: Vec<String> = results.iter().map(|summary| format!(
+        "    {{\"difficulty\": \"{}\", \"seed\": {}, \"outcome\": \"{}\", \"survival_seconds\": {:.2}, \"score\": {}, \"wave\": {}, \"deaths\": {}, \"damage_taken\": {}}}",
+        summary.difficulty.name(), summary.seed, summary.outcome(), summary.survival_seconds, summary.score, summary.wave, summary.deaths,
+        damage_json(|source| summary.damage_from(source))
+    )).collect();
+
+    let averages: Vec<String> = Difficulty::ALL.iter().filter_map(|difficulty| {
+        let runs: Vec<&RunSummary> = results.iter().filter(|summary| summary.difficulty == *difficulty).collect();
+        if runs.is_empty(){
+            return None;
+        }
+
+        let count = runs.len() as f32;
+        let mean = |value: &dyn Fn(&RunSummary) -> f32| runs.iter().map(|summary| value(summary)).sum::<f32>() / count;
+        Some(format!(
+            "    {{\"difficulty\": \"{}\", \"runs\": {}, \"game_overs\": {}, \"survival_seconds\": {:.2}, \"score\": {:.2}, \"wave\": {:.2}, \"damage_taken\": {}}}",
+            difficulty.name(), runs.len(), runs.iter().filter(|summary| summary.game_over).count(),
+            mean(&|summary| summary.survival_seconds), mean(&|summary| summary.score as f32), mean(&|summary| summary.wave as f32),
+            damage_json(|source| mean(&|summary| summary.damage_from(source)))
+        ))
+    }).collect();
+
+    format!("{{\n  \"runs\": [\n{}\n  ],\n  \"averages\": [\n{}\n  ]\n}}\n", runs.join(",\n"), averages.join(",\n"))
+}
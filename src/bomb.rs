@@ -0,0 +1,83 @@
+use bevy::prelude::*;
+use bevy::ecs::system::SystemParam;
+use crate::components::{ArchetypeName, Bullet, BulletPool, ComingFromEnemy, EffectKind, Enemy, EnemyKilled, ExplosionToSpawn, GameState, Health, Invulnerable, Player, PlayerIntent, PlayerState};
+use crate::{ARENA_HEIGHT, ARENA_WIDTH};
+use crate::utils::{GameSystem, ScaledTime};
+
+const BOMB_DAMAGE: f32 = 2.;
+const BOMB_INVULNERABILITY_SECONDS: f32 = 2.;
+const INVULNERABILITY_BLINK_SECONDS: f32 = 0.1;
+
+pub struct BombPlugin;
+
+impl Plugin for BombPlugin{
+    fn build(&self, app: &mut App) {
+        app
+        .add_system_set(
+            SystemSet::on_update(GameState::Playing)
+            .with_system(detonate_bomb.after(GameSystem::Input))
+            .with_system(update_invulnerability)
+        );
+    }
+}
+
+type BombedEnemy = (Entity, &'static Transform, &'static mut Health, Option<&'static ArchetypeName>);
+
+//everything a bomb touches
+#[derive(SystemParam)]
+struct BombTargets<'w, 's>{
+    enemies: Query<'w, 's, BombedEnemy, With<Enemy>>,
+    players: Query<'w, 's, (Entity, &'static Transform), With<Player>>,
+    enemy_bullets: Query<'w, 's, Entity, (With<Bullet>, With<ComingFromEnemy>)>,
+}
+
+//clears every enemy bullet and damages every enemy inside the arena
+fn detonate_bomb(mut commands: Commands, intent: Res<PlayerIntent>, mut player_state: ResMut<PlayerState>,
+    mut bullet_pool: ResMut<BulletPool>, mut explosions: EventWriter<ExplosionToSpawn>, mut kills: EventWriter<EnemyKilled>,
+    mut targets: BombTargets){
+
+    if !intent.bomb || player_state.bombs == 0{
+        return;
+    }
+
+    let (player_entity, player_transform) = match targets.players.get_single(){
+        Ok(player) => player,
+        Err(_) => return,
+    };
+
+    player_state.bombs -= 1;
+    explosions.send(ExplosionToSpawn(player_transform.translation, EffectKind::Bomb));
+    commands.entity(player_entity).insert(Invulnerable(Timer::from_seconds(BOMB_INVULNERABILITY_SECONDS, false)));
+
+    for bullet_entity in targets.enemy_bullets.iter(){
+        bullet_pool.release(&mut commands, bullet_entity);
+    }
+
+    for (enemy_entity, enemy_transform, mut enemy_health, archetype) in targets.enemies.iter_mut(){
+        let position = enemy_transform.translation;
+        if enemy_health.0 <= 0. || position.x.abs() > ARENA_WIDTH / 2. || position.y.abs() > ARENA_HEIGHT / 2.{
+            continue;
+        }
+
+        enemy_health.0 -= BOMB_DAMAGE;
+        if enemy_health.0 <= 0.{
+            commands.entity(enemy_entity).despawn();
+            kills.send(EnemyKilled { position, archetype: archetype.map(|archetype| archetype.0) });
+        }
+    }
+}
+
+//the player blinks while invulnerable
+fn update_invulnerability(mut commands: Commands, time: ScaledTime, mut query: Query<(Entity, &mut Invulnerable, &mut Visibility)>){
+    for (entity, mut invulnerable, mut visibility) in query.iter_mut(){
+        invulnerable.0.tick(time.delta());
+
+        if invulnerable.0.finished(){
+            visibility.is_visible = true;
+            commands.entity(entity).remove::<Invulnerable>();
+        }else{
+            let phase = (invulnerable.0.elapsed_secs() / (2. * INVULNERABILITY_BLINK_SECONDS)).fract();
+            visibility.is_visible = phase < 0.5;
+        }
+    }
+}
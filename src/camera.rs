@@ -0,0 +1,60 @@
+use bevy::prelude::*;
+use rand::{thread_rng, Rng};
+use crate::components::{CameraEffects, GameClock, MainCamera, PlayerHit, TimeScale};
+use crate::settings::GameSettings;
+
+const MAX_SHAKE_OFFSET: f32 = 18.;
+const MAX_SHAKE_ANGLE: f32 = 0.05;
+const TRAUMA_DECAY_PER_SECOND: f32 = 1.5;
+const HIT_STOP_TIME_SCALE: f32 = 0.05;
+
+pub const PLAYER_HIT_TRAUMA: f32 = 0.4;
+
+pub struct CameraEffectsPlugin;
+
+impl Plugin for CameraEffectsPlugin{
+    fn build(&self, app: &mut App) {
+        app
+        .insert_resource(CameraEffects::default())
+        .insert_resource(TimeScale(1.))
+        .add_system(update_hit_stop)
+        .add_system(shake_on_player_hit)
+        .add_system(shake_camera);
+    }
+}
+
+fn update_hit_stop(clock: Res<GameClock>, settings: Res<GameSettings>, mut effects: ResMut<CameraEffects>, mut time_scale: ResMut<TimeScale>){
+    if !settings.camera_effects{
+        effects.hit_stop = 0.;
+    }
+
+    //counted down in real time, the game clock is the one being slowed
+    effects.hit_stop = (effects.hit_stop - clock.delta_seconds()).max(0.);
+    time_scale.0 = if effects.hit_stop > 0. { HIT_STOP_TIME_SCALE } else { 1. };
+}
+
+//beams hit every frame with a sliver of damage, so trauma follows the damage dealt
+fn shake_on_player_hit(mut hits: EventReader<PlayerHit>, mut effects: ResMut<CameraEffects>){
+    for hit in hits.iter(){
+        effects.add_trauma(PLAYER_HIT_TRAUMA * hit.damage.min(1.));
+    }
+}
+
+fn shake_camera(time: Res<Time>, settings: Res<GameSettings>, mut effects: ResMut<CameraEffects>, mut query: Query<&mut Transform, With<MainCamera>>){
+    if !settings.camera_effects{
+        effects.trauma = 0.;
+    }
+
+    effects.trauma = (effects.trauma - TRAUMA_DECAY_PER_SECOND * time.delta_seconds()).max(0.);
+
+    //squared so small hits barely move the camera and big ones really kick
+    let shake = effects.trauma * effects.trauma;
+    //purely cosmetic, so it stays off the seeded game generator
+    let mut random_generator = thread_rng();
+
+    for mut transform in query.iter_mut(){
+        transform.translation.x = MAX_SHAKE_OFFSET * shake * random_generator.gen_range(-1.0..=1.0);
+        transform.translation.y = MAX_SHAKE_OFFSET * shake * random_generator.gen_range(-1.0..=1.0);
+        transform.rotation = Quat::from_rotation_z(MAX_SHAKE_ANGLE * shake * random_generator.gen_range(-1.0..=1.0));
+    }
+}
@@ -1,4 +1,8 @@
 use bevy::prelude::*;
+use bevy::utils::{Duration, HashMap};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use crate::{ENEMIES_PER_WAVE, PLAYER_LIVES, PLAYER_BOMBS, PLAYER_SHIELD_CAPACITY, PLAYER_MAX_SHIELD_CAPACITY, SHIELD_RECHARGE_DELAY};
+use crate::pool::EntityPool;
 
 #[derive(Component)]
 pub struct Bullet;
@@ -27,6 +31,15 @@ impl From<(f32, f32)> for SpriteSize{
 #[derive(Component)]
 pub struct Health(pub f32);
 
+impl Health{
+    //the shield soaks up what it can first, returns true once health is used up
+    //partly charged shields leave fractional damage, so health can step past zero without hitting it
+    pub fn take_damage(&mut self, damage: f32, shield: Option<&mut Shield>) -> bool{
+        self.0 -= shield.map_or(damage, |shield| shield.absorb(damage));
+        self.0 <= 0.
+    }
+}
+
 #[derive(Component)]
 pub struct FpsText;
 
@@ -35,11 +48,145 @@ pub struct ScoreText;
 pub struct Score(pub u32);
 
 #[derive(Component)]
-pub struct HealthText;
+pub struct WaveText;
+
+#[derive(Component)]
+pub struct LivesText;
+
+#[derive(Component)]
+pub struct BombText;
+
+#[derive(Component)]
+pub struct AchievementText;
+
+#[derive(Component)]
+pub struct HealthIcon(pub u32);
+
+#[derive(Component)]
+pub struct HudRoot;
+
+#[derive(Component)]
+pub struct LoadingScreen;
+
+#[derive(Component)]
+pub struct LoadingText;
+
+#[derive(Component)]
+pub struct ProgressBarFill;
+
+#[derive(Component)]
+pub struct MenuRoot;
+
+//index into the items of the menu currently shown
+#[derive(Component)]
+pub struct MenuItemText(pub usize);
+
+pub struct GameSeed(pub u64);
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum GameState{
+    Loading,
+    MainMenu,
+    Playing,
+    Paused,
+    Options,
+    GameOver,
+    AssetError,
+}
 
 #[derive(Component, Deref, DerefMut)]
 pub struct AnimationTimer(pub Timer);
 
+//Time as gameplay sees it. Follows the frame time, unless a fixed step is set,
+//which lets headless simulations run faster than real time
+#[derive(Default)]
+pub struct GameClock{
+    pub fixed_step: Option<f32>,
+    delta: f32,
+    elapsed: f64,
+}
+
+impl GameClock{
+    pub fn fixed(step: f32) -> Self{
+        Self { fixed_step: Some(step), ..default() }
+    }
+
+    pub fn advance(&mut self, frame_delta: f32){
+        self.delta = self.fixed_step.unwrap_or(frame_delta);
+        self.elapsed += self.delta as f64;
+    }
+
+    pub fn pause(&mut self){
+        self.delta = 0.;
+    }
+
+    pub fn delta_seconds(&self) -> f32{
+        self.delta
+    }
+
+    pub fn delta(&self) -> Duration{
+        Duration::from_secs_f32(self.delta)
+    }
+
+    pub fn seconds_since_startup(&self) -> f64{
+        self.elapsed
+    }
+}
+
+//every gameplay roll comes from here, so a seed reproduces a run
+#[derive(Deref, DerefMut)]
+pub struct GameRng(pub StdRng);
+
+impl GameRng{
+    //the generator state can't be written out, so it starts over from a fresh seed that can be
+    pub fn checkpoint(&mut self) -> u64{
+        let seed = self.0.gen();
+        self.0 = StdRng::seed_from_u64(seed);
+        seed
+    }
+}
+
+//CAMERA
+#[derive(Component)]
+pub struct MainCamera;
+
+#[derive(Default)]
+pub struct CameraEffects{
+    pub trauma: f32,
+    pub hit_stop: f32,
+}
+
+impl CameraEffects{
+    pub fn add_trauma(&mut self, amount: f32){
+        self.trauma = (self.trauma + amount).min(1.);
+    }
+
+    pub fn hit_stop(&mut self, seconds: f32){
+        self.hit_stop = self.hit_stop.max(seconds);
+    }
+}
+
+//multiplier for game time, dipped during hit-stop
+pub struct TimeScale(pub f32);
+
+//DIFFICULTY
+//Enemy parameters in effect right now, the preset scaled by the adaptive pressure
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CurrentDifficulty{
+    pub enemy_health: f32,
+    pub enemy_speed: f32,
+    pub spawn_interval: f32,
+    pub fire_chance: f64,
+    pub pressure: f32,
+}
+
+#[derive(Default)]
+pub struct AdaptiveDifficulty{
+    //pressure taken off after player deaths, decays back to zero over time
+    pub relief: f32,
+    pub deaths_seen: u32,
+}
+
 //PLAYER
 #[derive(Component)]
 pub struct Player;
@@ -47,21 +194,161 @@ pub struct Player;
 #[derive(Component)]
 pub struct ComingFromPlayer;
 
+//What the player should do this frame, written by the keyboard or by the autopilot
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PlayerIntent{
+    pub move_x: f32,
+    pub fire: bool,
+    pub missile: bool,
+    //held, the beam stays on while this is set
+    pub laser: bool,
+    pub bomb: bool,
+}
+
+//AUTOPILOT
+//attract_mode: started from the title screen, any key ends it
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Autopilot{
+    pub enabled: bool,
+    pub attract_mode: bool,
+    pub demo_seconds: f32,
+}
+
+#[derive(Component)]
+pub struct DemoText;
+
+//MISSILES
+//Bullet that steers towards a target, picking a new one when its target is gone
+#[derive(Component)]
+pub struct Homing{
+    pub target: Option<Entity>,
+    pub turn_rate: f32,
+    pub speed: f32,
+    pub lifetime: Timer,
+}
+
+//LASER
+//Continuous beam cast from its owner every frame, length is where the ray was stopped
+#[derive(Component)]
+pub struct LaserBeam{
+    pub owner: Entity,
+    pub direction: Vec2,
+    pub damage_per_second: f32,
+    pub origin: Vec2,
+    pub length: f32,
+    //enemy the beam is burning, moving onto another one counts as a new hit
+    pub target: Option<Entity>,
+}
+
+impl LaserBeam{
+    pub fn new(owner: Entity, direction: Vec2, damage_per_second: f32) -> Self{
+        Self { owner, direction: direction.normalize_or_zero(), damage_per_second, origin: Vec2::ZERO, length: 0., target: None }
+    }
+}
+
+//enemy beams burn for a while and then go out on their own
+#[derive(Component, Deref, DerefMut)]
+pub struct BeamDuration(pub Timer);
+
+//seconds of beam left, drained while firing
+#[derive(Component)]
+pub struct LaserEmitter{
+    pub energy: f32,
+}
+
+//ASTEROIDS
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AsteroidSize{
+    Large,
+    Medium,
+    Small,
+}
+
+#[derive(Component)]
+pub struct Asteroid(pub AsteroidSize);
+
+//rotation speed in radians per second
+#[derive(Component)]
+pub struct Spin(pub f32);
+
+//SHIELD
+//Absorbs hits before Health, recharges once it has not been hit for a while
+#[derive(Component)]
+pub struct Shield{
+    pub capacity: f32,
+    pub current: f32,
+    pub recharge_delay: f32,
+}
+
+impl Shield{
+    pub fn new(capacity: f32) -> Self{
+        Self { capacity, current: capacity, recharge_delay: 0. }
+    }
+
+    //returns the damage left over for Health
+    pub fn absorb(&mut self, damage: f32) -> f32{
+        let absorbed = damage.min(self.current);
+        self.current -= absorbed;
+        self.recharge_delay = SHIELD_RECHARGE_DELAY;
+        damage - absorbed
+    }
+
+    pub fn recharge(&mut self, amount: f32, dt: f32){
+        if self.recharge_delay > 0.{
+            self.recharge_delay = (self.recharge_delay - dt).max(0.);
+        }else{
+            self.current = (self.current + amount).min(self.capacity);
+        }
+    }
+
+    pub fn upgrade(&mut self, amount: f32){
+        self.capacity = (self.capacity + amount).min(PLAYER_MAX_SHIELD_CAPACITY);
+        self.current = self.capacity;
+    }
+}
+
+#[derive(Component)]
+pub struct ShieldBubble;
+
+//PICKUPS
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PickupKind{
+    ShieldUpgrade,
+}
+
+#[derive(Component)]
+pub struct Pickup(pub PickupKind);
+
+//hits are ignored until the timer runs out
+#[derive(Component)]
+pub struct Invulnerable(pub Timer);
+
 pub struct PlayerState{
     pub is_alive: bool,
     pub last_shot: f64,
+    pub lives: u32,
+    pub deaths: u32,
+    pub bombs: u32,
+    //kept between lives so shield upgrades survive a respawn
+    pub shield_capacity: f32,
+    //laser charge for the next ship, only set when a saved run is resumed
+    pub laser_energy: Option<f32>,
 }
 
 impl Default for PlayerState{
     fn default() -> Self {
-        Self { is_alive: false, last_shot: -1. }
+        Self { is_alive: false, last_shot: -1., lives: PLAYER_LIVES, deaths: 0, bombs: PLAYER_BOMBS, shield_capacity: PLAYER_SHIELD_CAPACITY, laser_energy: None }
     }
 }
 
 impl PlayerState{
-    pub fn player_is_shot(&mut self, time: f64){
+    //returns true when that was the last life
+    pub fn player_is_shot(&mut self, time: f64) -> bool{
         self.is_alive = false;
         self.last_shot = time;
+        self.lives = self.lives.saturating_sub(1);
+        self.deaths += 1;
+        self.lives == 0
     }
 
     pub fn spawned(&mut self){
@@ -85,13 +372,98 @@ pub struct ComingFromEnemy;
 #[derive(Component)]
 pub struct EnemySpawnPosition(pub (f32, f32));
 
+//How an enemy moves and attacks, picked from the archetype table at spawn
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EnemyBehavior{
+    Drift,
+    Formation,
+    Kamikaze,
+    Turret,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct EnemyArchetype{
+    pub name: &'static str,
+    pub weight: u32,
+    pub health: f32,
+    pub speed: f32,
+    pub tint: Color,
+    pub behavior: EnemyBehavior,
+    //played when it is killed, large enemies shake the camera harder and freeze time for a moment
+    pub death_effect: EffectKind,
+}
+
+//name of the archetype an enemy was spawned from, for the stats
+//also carried by the bullets it fires, so damage taken can be traced back to it
+#[derive(Component, Clone, Copy)]
+pub struct ArchetypeName(pub &'static str);
+
+//holds a slot of the shared formation grid until it peels off to dive
+#[derive(Component)]
+pub struct FormationFlyer{
+    pub slot: usize,
+    pub dive_timer: Timer,
+    pub diving: bool,
+}
+
+//steers towards the player, limited by its turn rate
+#[derive(Component)]
+pub struct Kamikaze{
+    pub turn_rate: f32,
+    pub speed: f32,
+}
+
+//flies in, holds its position while shooting at the player, then leaves
+#[derive(Component)]
+pub struct Turret{
+    pub hold_y: f32,
+    pub fire_timer: Timer,
+    pub shots_left: u32,
+}
+
+//the formation grid sways as a whole
+#[derive(Default)]
+pub struct Formation{
+    pub sway_phase: f32,
+}
+
 pub struct EnemyCount(pub u32);
 
+pub struct Wave{
+    pub number: u32,
+    pub kills: u32,
+}
+
+impl Default for Wave{
+    fn default() -> Self {
+        Self { number: 1, kills: 0 }
+    }
+}
+
+impl Wave{
+    //returns true when the kill finished the wave
+    pub fn enemy_killed(&mut self) -> bool{
+        self.kills += 1;
+        if self.kills >= ENEMIES_PER_WAVE{
+            self.number += 1;
+            self.kills = 0;
+            return true;
+        }
+        false
+    }
+}
+
+
+//POOLS
+pub type BulletPool = EntityPool<(Bullet, MovableObject, Velocity, SpriteSize, ComingFromPlayer, ComingFromEnemy, Homing, ArchetypeName)>;
+pub type ExplosionPool = EntityPool<(Explosion, ExplosionTimer)>;
+
 
 pub struct GameTextures{
    pub player: Handle<TextureAtlas>,
    pub enemy: Handle<TextureAtlas>,
-   pub explosion: Handle<TextureAtlas>,
+   pub shield: Handle<Image>,
+   pub asteroid: Handle<Image>,
 }
 
 
@@ -99,14 +471,174 @@ pub struct GameTextures{
 #[derive(Component)]
 pub struct Explosion;
 
-#[derive(Component)]
-pub struct ExplosionToSpawn(pub Vec3, pub f32);
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EffectKind{
+    BulletHit,
+    Enemy,
+    Player,
+    LargeEnemy,
+    Bomb,
+}
+
+#[derive(Clone)]
+pub struct EffectDefinition{
+    pub atlas: Handle<TextureAtlas>,
+    pub frame_duration: f32,
+    pub scale: f32,
+    pub tint: Color,
+    pub z_layer: f32,
+    pub particles: Option<ParticleEmitter>,
+    pub trauma: f32,
+    pub hit_stop: f32,
+}
+
+pub struct GameEffects(pub HashMap<EffectKind, EffectDefinition>);
+
+//sent as an event, played by explosion_spawn
+pub struct ExplosionToSpawn(pub Vec3, pub EffectKind);
 
 #[derive(Component)]
 pub struct ExplosionTimer(pub Timer);
 
-impl Default for ExplosionTimer {
-	fn default() -> Self {
-		Self(Timer::from_seconds(0.05, true))
-	}
-}
\ No newline at end of file
+
+//PARTICLES
+#[derive(Component, Clone)]
+pub struct ParticleEmitter{
+    pub spawn_rate: f32,
+    pub burst_count: u32,
+    pub lifetime: f32,
+    pub offset: Vec2,
+    pub direction: f32,
+    pub spread: f32,
+    pub speed: (f32, f32),
+    pub start_color: Color,
+    pub end_color: Color,
+    pub start_size: f32,
+    pub end_size: f32,
+    pub accumulator: f32,
+}
+
+impl ParticleEmitter{
+    //continuous emission, returns the particles due for this tick
+    pub fn tick<R: Rng>(&mut self, delta: f32, random_generator: &mut R) -> Vec<Particle>{
+        self.accumulator += self.spawn_rate * delta;
+        let count = self.accumulator.floor();
+        self.accumulator -= count;
+
+        (0..count as u32).map(|_| self.particle(random_generator)).collect()
+    }
+
+    pub fn burst<R: Rng>(&self, random_generator: &mut R) -> Vec<Particle>{
+        (0..self.burst_count).map(|_| self.particle(random_generator)).collect()
+    }
+
+    fn particle<R: Rng>(&self, random_generator: &mut R) -> Particle{
+        let angle = self.direction + random_generator.gen_range(-self.spread..=self.spread);
+        let speed = random_generator.gen_range(self.speed.0..=self.speed.1);
+
+        Particle{
+            velocity: Vec2::new(angle.cos(), angle.sin()) * speed,
+            age: 0.,
+            lifetime: self.lifetime,
+            start_color: self.start_color,
+            end_color: self.end_color,
+            start_size: self.start_size,
+            end_size: self.end_size,
+        }
+    }
+}
+
+#[derive(Component, Clone)]
+pub struct Particle{
+    pub velocity: Vec2,
+    pub age: f32,
+    pub lifetime: f32,
+    pub start_color: Color,
+    pub end_color: Color,
+    pub start_size: f32,
+    pub end_size: f32,
+}
+
+impl Particle{
+    //returns false once the particle has outlived its lifetime
+    pub fn advance(&mut self, delta: f32) -> bool{
+        self.age += delta;
+        self.age < self.lifetime
+    }
+
+    pub fn progress(&self) -> f32{
+        if self.lifetime <= 0.{
+            return 1.;
+        }
+        (self.age / self.lifetime).clamp(0., 1.)
+    }
+
+    pub fn color(&self) -> Color{
+        let t = self.progress();
+        let start = self.start_color.as_rgba_f32();
+        let end = self.end_color.as_rgba_f32();
+        let lerp = |i: usize| start[i] + (end[i] - start[i]) * t;
+
+        Color::rgba(lerp(0), lerp(1), lerp(2), lerp(3))
+    }
+
+    pub fn size(&self) -> f32{
+        self.start_size + (self.end_size - self.start_size) * self.progress()
+    }
+}
+
+
+//BACKGROUND
+#[derive(Component)]
+pub struct ParallaxLayer{
+    pub speed: f32,
+    pub wrap_height: f32,
+}
+
+pub struct StarfieldSettings{
+    pub layers: u32,
+    pub stars_per_layer: u32,
+    pub nebula_image: Option<String>,
+}
+
+impl Default for StarfieldSettings{
+    fn default() -> Self {
+        Self { layers: 3, stars_per_layer: 60, nebula_image: None }
+    }
+}
+
+
+//EVENTS
+//Sent by the systems that cause them, score, stats, audio and effects each read them on their own
+pub struct EnemyKilled{
+    pub position: Vec3,
+    pub archetype: Option<&'static str>,
+}
+
+//a player bullet, missile or laser beam hit an enemy
+pub struct EnemyHit{
+    pub position: Vec3,
+}
+
+//a player bullet shot down an enemy bullet
+pub struct BulletCancelled{
+    pub position: Vec3,
+}
+
+pub struct PlayerHit{
+    pub damage: f32,
+    //archetype of the enemy behind the hit, "asteroid" for asteroids
+    pub source: Option<&'static str>,
+}
+
+pub struct PlayerDied{
+    pub position: Vec3,
+}
+
+pub struct BulletFired{
+    pub from_player: bool,
+}
+
+pub struct WaveCleared{
+    pub number: u32,
+}
@@ -0,0 +1,69 @@
+use bevy::prelude::*;
+use bevy::diagnostic::{Diagnostics, EntityCountDiagnosticsPlugin};
+use crate::components::{BulletPool, CurrentDifficulty, EnemyCount, ExplosionPool, GameState};
+
+const OVERLAY_FONT: &str = "fonts/FiraSans-Regular.ttf";
+const OVERLAY_FONT_SIZE: f32 = 14.;
+const OVERLAY_COLOR: Color = Color::rgb(0.5, 1.0, 0.5);
+const OVERLAY_TOGGLE_KEY: KeyCode = KeyCode::F3;
+
+//only built with the debug-overlay feature
+pub struct DebugOverlayPlugin;
+
+impl Plugin for DebugOverlayPlugin{
+    fn build(&self, app: &mut App) {
+        app
+        .add_plugin(EntityCountDiagnosticsPlugin)
+        .add_startup_system(spawn_debug_overlay)
+        .add_system(toggle_debug_overlay)
+        .add_system(update_debug_overlay);
+    }
+}
+
+#[derive(Component)]
+pub struct DebugOverlayText;
+
+//outside the hud root, so it stays in the window corner whatever the arena letterboxing
+fn spawn_debug_overlay(mut commands: Commands, asset_server: Res<AssetServer>){
+    commands.spawn_bundle(TextBundle{
+        style: Style{
+            position_type: PositionType::Absolute,
+            position: Rect { bottom: Val::Px(4.), left: Val::Px(4.), ..default() },
+            ..default()
+        },
+        text: Text::with_section("", TextStyle{
+            font: asset_server.load(OVERLAY_FONT),
+            font_size: OVERLAY_FONT_SIZE,
+            color: OVERLAY_COLOR,
+        }, default()),
+        ..default()
+    })
+    .insert(DebugOverlayText);
+}
+
+fn toggle_debug_overlay(input: Res<Input<KeyCode>>, mut query: Query<&mut Visibility, With<DebugOverlayText>>){
+    if input.just_pressed(OVERLAY_TOGGLE_KEY){
+        for mut visibility in query.iter_mut(){
+            visibility.is_visible = !visibility.is_visible;
+        }
+    }
+}
+
+fn update_debug_overlay(diagnostics: Res<Diagnostics>, state: Res<State<GameState>>, difficulty: Res<CurrentDifficulty>, enemy_count: Res<EnemyCount>,
+    bullet_pool: Res<BulletPool>, explosion_pool: Res<ExplosionPool>, mut query: Query<&mut Text, With<DebugOverlayText>>){
+
+    let entities = diagnostics.get(EntityCountDiagnosticsPlugin::ENTITY_COUNT)
+        .and_then(|entities| entities.value())
+        .unwrap_or(0.);
+    let bullets = bullet_pool.metrics();
+    let explosions = explosion_pool.metrics();
+
+    let overlay = format!(
+        "state: {:?}\nentities: {}\nenemies: {}\nbullet pool: {} (peak {})\nexplosion pool: {} (peak {})\npressure: {:.2}",
+        state.current(), entities, enemy_count.0, bullets.size, bullets.high_water_mark, explosions.size, explosions.high_water_mark, difficulty.pressure
+    );
+
+    for mut text in query.iter_mut(){
+        text.sections[0].value = overlay.clone();
+    }
+}
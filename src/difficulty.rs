@@ -0,0 +1,85 @@
+use bevy::prelude::*;
+use crate::components::{AdaptiveDifficulty, CurrentDifficulty, GameClock, GameState, PlayerState, Score};
+use crate::settings::{Difficulty, GameSettings};
+
+//adaptive mode: pressure grows with score and is eased off by player deaths
+const SCORE_PER_PRESSURE_STEP: u32 = 10;
+const PRESSURE_PER_STEP: f32 = 0.05;
+const MAX_SCORE_PRESSURE: f32 = 1.;
+const RELIEF_PER_DEATH: f32 = 0.25;
+const MAX_RELIEF: f32 = 0.6;
+const RELIEF_DECAY_PER_SECOND: f32 = 0.01;
+const MIN_PRESSURE: f32 = 0.5;
+
+pub struct DifficultyPlugin;
+
+impl Plugin for DifficultyPlugin{
+    fn build(&self, app: &mut App) {
+        app
+        .insert_resource(Difficulty::Normal.preset().scaled(1.))
+        .insert_resource(AdaptiveDifficulty::default())
+        .add_system(update_difficulty);
+    }
+}
+
+//Enemy parameters of a difficulty before any adaptive scaling
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DifficultyPreset{
+    pub enemy_health: f32,
+    pub enemy_speed: f32,
+    pub spawn_interval: f32,
+    pub fire_chance: f64,
+}
+
+impl Difficulty{
+    pub fn preset(&self) -> DifficultyPreset{
+        match self{
+            Difficulty::Easy => DifficultyPreset { enemy_health: 1., enemy_speed: 0.8, spawn_interval: 0.8, fire_chance: 1. / 200. },
+            Difficulty::Normal => DifficultyPreset { enemy_health: 2., enemy_speed: 1., spawn_interval: 0.5, fire_chance: 1. / 100. },
+            Difficulty::Hard => DifficultyPreset { enemy_health: 3., enemy_speed: 1.3, spawn_interval: 0.35, fire_chance: 1. / 60. },
+        }
+    }
+}
+
+impl DifficultyPreset{
+    //pressure above 1 makes enemies tougher, faster, more frequent and more trigger happy
+    pub fn scaled(&self, pressure: f32) -> CurrentDifficulty{
+        CurrentDifficulty{
+            enemy_health: (self.enemy_health * pressure).round().max(1.),
+            enemy_speed: self.enemy_speed * pressure,
+            spawn_interval: self.spawn_interval / pressure,
+            fire_chance: (self.fire_chance * pressure as f64).min(1.),
+            pressure,
+        }
+    }
+}
+
+impl AdaptiveDifficulty{
+    pub fn pressure(&self, score: u32) -> f32{
+        let score_pressure = ((score / SCORE_PER_PRESSURE_STEP) as f32 * PRESSURE_PER_STEP).min(MAX_SCORE_PRESSURE);
+        (1. + score_pressure - self.relief).max(MIN_PRESSURE)
+    }
+}
+
+fn update_difficulty(clock: Res<GameClock>, state: Res<State<GameState>>, settings: Res<GameSettings>, score: Res<Score>, player_state: Res<PlayerState>,
+    mut adaptive: ResMut<AdaptiveDifficulty>, mut current: ResMut<CurrentDifficulty>){
+
+    //a new run starts the death count over
+    if player_state.deaths < adaptive.deaths_seen{
+        *adaptive = AdaptiveDifficulty::default();
+    }
+
+    let new_deaths = player_state.deaths - adaptive.deaths_seen;
+    adaptive.deaths_seen = player_state.deaths;
+    adaptive.relief = (adaptive.relief + new_deaths as f32 * RELIEF_PER_DEATH).min(MAX_RELIEF);
+    if *state.current() == GameState::Playing{
+        adaptive.relief = (adaptive.relief - RELIEF_DECAY_PER_SECOND * clock.delta_seconds()).max(0.);
+    }
+
+    let pressure = if settings.adaptive_difficulty { adaptive.pressure(score.0) } else { 1. };
+    let difficulty = settings.difficulty.preset().scaled(pressure);
+
+    if *current != difficulty{
+        *current = difficulty;
+    }
+}
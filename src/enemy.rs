@@ -1,15 +1,28 @@
 
-use bevy::utils::HashSet;
-use bevy::{prelude::*, core::FixedTimestep, ecs::schedule::ShouldRun, sprite::collide_aabb::collide};
-use rand::{thread_rng, Rng};
-use crate::*;
+use bevy::{prelude::*, ecs::schedule::ShouldRun, sprite::collide_aabb::collide};
+use rand::Rng;
+use crate::components::{AnimationTimer, ArchetypeName, Bullet, BulletFired, BulletPool, ComingFromEnemy, CurrentDifficulty, EffectKind, Enemy, EnemyArchetype, EnemyBehavior, EnemyCount, EnemySpawnPosition, ExplosionToSpawn, FormationFlyer, GameClock, GameRng, GameState, GameTextures, Health, Kamikaze, MovableObject, PlayerDied, PlayerHit, SpriteSize, Turret, Velocity};
+use crate::enemy_ai::{FORMATION_DIVE_DELAY, FORMATION_SLOTS, KAMIKAZE_TURN_RATE, TURRET_FIRE_INTERVAL, TURRET_HOLD_HEIGHT, TURRET_SHOTS, formation_slot_position};
+use crate::{ARENA_HEIGHT, ARENA_WIDTH, MAXIMUM_NUM_OF_ENEMIES_IN_ARENA};
+use crate::player::{ShieldedPlayer, VulnerablePlayer};
+use crate::utils::run_if_playing;
 
 
 const ENEMY_SIZE: (f32, f32) = (64., 64.);
 const ENEMY_SCALE: f32 = 1.;
+const ENEMY_SPEED: f32 = 0.3;
 
 const ENEMY_BULLET_SIZE: (f32, f32) = (10., 10.);
 const ENEMY_BULLET_COLOR: Color = Color::rgb(1.0, 0.5, 0.58);
+const ENEMY_BULLET_SPEED: f32 = 1.;
+
+//Spawn table, an archetype is picked with a chance proportional to its weight
+pub const ENEMY_ARCHETYPES: &[EnemyArchetype] = &[
+    EnemyArchetype { name: "drifter", weight: 5, health: 1., speed: 1., tint: Color::WHITE, behavior: EnemyBehavior::Drift, death_effect: EffectKind::Enemy },
+    EnemyArchetype { name: "escort", weight: 3, health: 1., speed: 1., tint: Color::rgb(0.7, 1.0, 0.7), behavior: EnemyBehavior::Formation, death_effect: EffectKind::Enemy },
+    EnemyArchetype { name: "kamikaze", weight: 2, health: 0.5, speed: 1.8, tint: Color::rgb(1.0, 0.6, 0.6), behavior: EnemyBehavior::Kamikaze, death_effect: EffectKind::Enemy },
+    EnemyArchetype { name: "turret", weight: 1, health: 2., speed: 1., tint: Color::rgb(0.7, 0.7, 1.0), behavior: EnemyBehavior::Turret, death_effect: EffectKind::LargeEnemy },
+];
 
 pub struct EnemyPlugin;
 
@@ -18,7 +31,7 @@ impl Plugin for EnemyPlugin{
         app
         .add_system_set(
             SystemSet::new()
-            .with_run_criteria(FixedTimestep::step(0.5))
+            .with_run_criteria(enemy_spawn_criteria.chain(run_if_playing))
             .with_system(spawn_enemy)
         )
         .add_system_set(
@@ -26,129 +39,182 @@ impl Plugin for EnemyPlugin{
             .with_run_criteria(enemy_fire_criteria)
             .with_system(enemy_fire)
         )
-        .add_system(enemy_movement)
-        .add_system(enemy_bullet_despawn)
-        .add_system(enemy_bullet_colision);
+        .add_system_set(
+            SystemSet::on_update(GameState::Playing)
+            .with_system(enemy_bullet_colision)
+        );
     }
 }
 
-pub fn spawn_enemy(commands: Commands, texture: Res<GameTextures>, enemy_count: ResMut<EnemyCount>){
+//spawn interval follows the current difficulty, so a fixed timestep won't do
+fn enemy_spawn_criteria(clock: Res<GameClock>, difficulty: Res<CurrentDifficulty>, mut elapsed: Local<f32>) -> ShouldRun{
+    *elapsed += clock.delta_seconds();
+    if *elapsed >= difficulty.spawn_interval{
+        *elapsed = 0.;
+        ShouldRun::Yes
+    }else{
+        ShouldRun::No
+    }
+}
+
+pub fn spawn_enemy(mut commands: Commands, texture: Res<GameTextures>, difficulty: Res<CurrentDifficulty>, mut enemy_count: ResMut<EnemyCount>, mut random_generator: ResMut<GameRng>, formation_query: Query<&FormationFlyer>){
 
     if enemy_count.0 < MAXIMUM_NUM_OF_ENEMIES_IN_ARENA{
 
-        let mut random_generator = thread_rng();
-        let position_x: f32 = random_generator.gen_range(((-WINDOW_WIDTH/2f32) + 20f32)..((WINDOW_WIDTH/2f32) - 20f32));
-        let position_y: f32 = WINDOW_HEIGHT;
+        let mut archetype = pick_archetype(&mut random_generator.0);
+        let mut position = Vec2::new(random_generator.gen_range(((-ARENA_WIDTH/2f32) + 20f32)..((ARENA_WIDTH/2f32) - 20f32)), ARENA_HEIGHT);
+
+        //formation flyers need a free slot in the grid, otherwise they fall back to drifting
+        let mut slot = None;
+        if archetype.behavior == EnemyBehavior::Formation{
+            slot = (0..FORMATION_SLOTS).find(|slot| formation_query.iter().all(|flyer| flyer.slot != *slot));
+            match slot{
+                Some(slot) => position.x = formation_slot_position(slot, 0.).x,
+                None => archetype = &ENEMY_ARCHETYPES[0],
+            }
+        }
 
-        spawn_enemy_entity(commands, texture, enemy_count, position_x, position_y);
+        spawn_enemy_entity(&mut commands, &texture, &difficulty, archetype, slot, position, &mut random_generator.0);
+        enemy_count.0 += 1;
     }
 
 }
 
-fn spawn_enemy_entity(mut commands: Commands, texture: Res<GameTextures>, mut enemy_count: ResMut<EnemyCount>, position_x: f32, position_y: f32){
+pub fn archetype_named(name: &str) -> Option<&'static EnemyArchetype>{
+    ENEMY_ARCHETYPES.iter().find(|archetype| archetype.name == name)
+}
 
+fn pick_archetype<R: Rng>(random_generator: &mut R) -> &'static EnemyArchetype{
+    let total: u32 = ENEMY_ARCHETYPES.iter().map(|archetype| archetype.weight).sum();
+    let mut roll = random_generator.gen_range(0..total);
 
-    commands.spawn_bundle(SpriteSheetBundle{
+    for archetype in ENEMY_ARCHETYPES{
+        if roll < archetype.weight{
+            return archetype;
+        }
+        roll -= archetype.weight;
+    }
+    &ENEMY_ARCHETYPES[0]
+}
+
+pub fn spawn_enemy_entity<R: Rng>(commands: &mut Commands, texture: &GameTextures, difficulty: &CurrentDifficulty, archetype: &EnemyArchetype, slot: Option<usize>, position: Vec2, random_generator: &mut R) -> Entity{
+
+    let speed = ENEMY_SPEED * difficulty.enemy_speed * archetype.speed;
+
+    let mut enemy = commands.spawn_bundle(SpriteSheetBundle{
         texture_atlas: texture.enemy.clone(),
+        sprite: TextureAtlasSprite{
+            color: archetype.tint,
+            ..default()
+        },
         transform: Transform{
-            translation: Vec3::new(position_x, position_y, 10.),
+            translation: position.extend(10.),
             scale: Vec3::new(ENEMY_SCALE, ENEMY_SCALE, 1.),
             ..default()
         },
         ..default()
-    })
+    });
+    enemy
     .insert(Enemy)
+    .insert(ArchetypeName(archetype.name))
     .insert(SpriteSize::from(ENEMY_SIZE))
-    .insert(Health(2.))
-    .insert(Velocity {x: 0., y: -0.3})
-    .insert(EnemySpawnPosition((position_x, position_y)))
+    .insert(Health((difficulty.enemy_health * archetype.health).round().max(1.)))
+    .insert(Velocity {x: 0., y: -speed})
+    .insert(MovableObject {auto_despawn: true})
+    .insert(EnemySpawnPosition((position.x, position.y)))
     .insert(AnimationTimer(Timer::from_seconds(0.1, true)));
 
-    enemy_count.0 += 1;
-}
-
-fn enemy_movement(mut commands: Commands, mut enemy_count: ResMut<EnemyCount>, mut query: Query<(Entity, &Velocity, &mut Transform), With<Enemy>>){
-    
-    for(_enemy_entity, velocity ,mut enemy_transform) in query.iter_mut(){
-        let enemy_position = &mut enemy_transform.translation;
-
-        enemy_position.y += velocity.y * TIME_STEP * GAME_SPEED;
-
-        if  enemy_position.y < -WINDOW_HEIGHT {
-            commands.entity(_enemy_entity).despawn();
-            enemy_count.0 -= 1;
-        }
-
+    match archetype.behavior{
+        EnemyBehavior::Drift => {},
+        EnemyBehavior::Formation => {
+            enemy.insert(FormationFlyer{
+                slot: slot.unwrap_or(0),
+                dive_timer: Timer::from_seconds(random_generator.gen_range(FORMATION_DIVE_DELAY.0..FORMATION_DIVE_DELAY.1), false),
+                diving: false,
+            });
+        },
+        EnemyBehavior::Kamikaze => {
+            enemy.insert(Kamikaze { turn_rate: KAMIKAZE_TURN_RATE, speed });
+        },
+        EnemyBehavior::Turret => {
+            enemy.insert(Turret{
+                hold_y: random_generator.gen_range(TURRET_HOLD_HEIGHT.0..TURRET_HOLD_HEIGHT.1),
+                fire_timer: Timer::from_seconds(TURRET_FIRE_INTERVAL / difficulty.pressure, true),
+                shots_left: TURRET_SHOTS,
+            });
+        },
     }
+
+    enemy.id()
 }
 
-fn enemy_fire_criteria() -> ShouldRun{
-    if thread_rng().gen_bool(1. / 100.){
+//only rolls while playing, so the seeded generator sees the same draws on every run
+fn enemy_fire_criteria(state: Res<State<GameState>>, difficulty: Res<CurrentDifficulty>, mut random_generator: ResMut<GameRng>) -> ShouldRun{
+    if *state.current() == GameState::Playing && random_generator.gen_bool(difficulty.fire_chance){
         ShouldRun::Yes
     }else{
         ShouldRun::No
     }
 }
 
-fn enemy_fire(mut commands: Commands, query: Query<&Transform, With<Enemy>>){
+type Shooter = (&'static Transform, Option<&'static ArchetypeName>);
 
-    for &transform in query.iter(){
-
-        let enemy_postion: (f32, f32) = (transform.translation.x, transform.translation.y);
-
-        //spawn bullet
-        commands.spawn_bundle(SpriteBundle{
-            sprite: Sprite{
-                color: ENEMY_BULLET_COLOR,
-                custom_size: Some(Vec2::new(ENEMY_BULLET_SIZE.0, ENEMY_BULLET_SIZE.1)),
-                ..default()
-            },
-            transform: Transform{
-                translation: Vec3::new(enemy_postion.0, enemy_postion.1 - 25., 1.0),
-                scale: Vec3::new(1., 1., 1.),
-                ..default()
-            },
-            ..default()
-        })
-        .insert(ComingFromEnemy)
-        .insert(SpriteSize::from(ENEMY_BULLET_SIZE))
-        .insert(Bullet)
-        .insert(MovableObject {auto_despawn: true})
-        .insert(Velocity {x: 0. , y: -1.}); //COMMON COMPONENT
+//turrets aim their own shots
+fn enemy_fire(mut commands: Commands, mut bullet_pool: ResMut<BulletPool>, mut bullets_fired: EventWriter<BulletFired>, query: Query<Shooter, (With<Enemy>, Without<Turret>)>){
 
+    for (transform, archetype) in query.iter(){
+        let bullet = fire_enemy_bullet(&mut commands, &mut bullet_pool, transform.translation, Vec2::new(0., -1.));
+        if let Some(archetype) = archetype{
+            commands.entity(bullet).insert(*archetype);
+        }
+        bullets_fired.send(BulletFired { from_player: false });
     }
 }
 
-fn enemy_bullet_despawn(mut commands: Commands, query: Query<(Entity, &Transform), (With<Bullet>, With<ComingFromEnemy>)>)
-{
-    let mut despawned_entitites: HashSet<Entity> = HashSet::new();
+pub fn fire_enemy_bullet(commands: &mut Commands, bullet_pool: &mut BulletPool, enemy_position: Vec3, direction: Vec2) -> Entity{
 
-    for(bullet_entity, bullet_transform) in query.iter(){
-        if despawned_entitites.contains(&bullet_entity){
-            continue;
-        }
-        
-        let bullet_position = &bullet_transform.translation;
+    let velocity = direction.normalize_or_zero() * ENEMY_BULLET_SPEED;
 
-        if bullet_position.y < (-WINDOW_HEIGHT/2.) {
-           commands.entity(bullet_entity).despawn();
-           despawned_entitites.insert(bullet_entity);
-        }
-    }
+    //spawn bullet
+    let bullet = bullet_pool.acquire(commands);
+    commands.entity(bullet).insert_bundle(SpriteBundle{
+        sprite: Sprite{
+            color: ENEMY_BULLET_COLOR,
+            custom_size: Some(Vec2::new(ENEMY_BULLET_SIZE.0, ENEMY_BULLET_SIZE.1)),
+            ..default()
+        },
+        transform: Transform{
+            translation: Vec3::new(enemy_position.x, enemy_position.y - 25., 1.0),
+            scale: Vec3::new(1., 1., 1.),
+            ..default()
+        },
+        ..default()
+    })
+    .insert(ComingFromEnemy)
+    .insert(SpriteSize::from(ENEMY_BULLET_SIZE))
+    .insert(Bullet)
+    .insert(MovableObject {auto_despawn: true})
+    .insert(Velocity {x: velocity.x , y: velocity.y}); //COMMON COMPONENT
+
+    bullet
 }
+
+type EnemyBulletHit = (Entity, &'static Transform, &'static SpriteSize, Option<&'static ArchetypeName>);
+
 fn enemy_bullet_colision(mut commands: Commands, 
-    mut player_state: ResMut<PlayerState>,
-    mut score: ResMut<Score>,
-    time: Res<Time>,
-    mut player_query: Query<(Entity, &Transform, &SpriteSize, &mut Health), With<Player>>, 
-    bullet_query: Query<(Entity, &Transform, &SpriteSize), (With<Bullet>, With<ComingFromEnemy>)>){
+    mut bullet_pool: ResMut<BulletPool>,
+    mut explosions: EventWriter<ExplosionToSpawn>,
+    mut player_hits: EventWriter<PlayerHit>,
+    mut player_deaths: EventWriter<PlayerDied>,
+    mut player_query: Query<ShieldedPlayer, VulnerablePlayer>, 
+    bullet_query: Query<EnemyBulletHit, (With<Bullet>, With<ComingFromEnemy>)>){
 
-    for(player_entity, player_transform, player_size, mut player_health) in player_query.iter_mut(){
+    for(player_entity, player_transform, player_size, mut player_health, mut shield) in player_query.iter_mut(){
 
         let player_scales: (f32, f32) = (player_transform.scale.x, player_transform.scale.y);
         let player_scale = Vec2::new(player_scales.0, player_scales.1);    
 
-        for (bullet_entity, bullet_transform, bullet_size) in bullet_query.iter(){
+        for (bullet_entity, bullet_transform, bullet_size, archetype) in bullet_query.iter(){
 
             let bullet_scales: (f32, f32) = (bullet_transform.scale.x, bullet_transform.scale.y);
             let bullet_scale = Vec2::new(bullet_scales.0, bullet_scales.1);
@@ -160,21 +226,19 @@ fn enemy_bullet_colision(mut commands: Commands,
 
             //colision logic
 
-            if let Some(_) = collision{
-
-                player_health.0 -= 1.;
-                if player_health.0 == 0.{
+            if collision.is_some(){
 
+                let depleted = player_health.take_damage(1., shield.as_deref_mut());
+                player_hits.send(PlayerHit { damage: 1., source: archetype.map(|archetype| archetype.0) });
+                if depleted{
 
-                    commands.spawn().insert(ExplosionToSpawn(player_transform.translation.clone(), 1.5f32));
-                    commands.entity(player_entity).despawn(); 
 
-                    score.0 = 0;
-                    player_state.player_is_shot(time.seconds_since_startup());
+                    commands.entity(player_entity).despawn_recursive(); 
+                    player_deaths.send(PlayerDied { position: player_transform.translation });
                 }               
             
-                commands.spawn().insert(ExplosionToSpawn(bullet_transform.translation.clone(), 0.5f32));
-                commands.entity(bullet_entity).despawn();
+                explosions.send(ExplosionToSpawn(bullet_transform.translation, EffectKind::BulletHit));
+                bullet_pool.release(&mut commands, bullet_entity);
             }
         }
     }
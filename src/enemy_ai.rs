@@ -0,0 +1,174 @@
+use bevy::prelude::*;
+use std::f32::consts::FRAC_PI_2;
+use crate::components::{ArchetypeName, BulletFired, BulletPool, Enemy, Formation, FormationFlyer, GameState, Kamikaze, LaserBeam, Player, Turret, Velocity};
+use crate::enemy::fire_enemy_bullet;
+use crate::{ARENA_HEIGHT, GAME_SPEED, TIME_STEP};
+use crate::laser::fire_enemy_beam;
+use crate::missile::{MissileOwner, fire_missile};
+use crate::utils::{GameSystem, ScaledTime};
+
+const FORMATION_COLUMNS: usize = 5;
+const FORMATION_ROWS: usize = 2;
+pub const FORMATION_SLOTS: usize = FORMATION_COLUMNS * FORMATION_ROWS;
+const FORMATION_SPACING: (f32, f32) = (80., 70.);
+const FORMATION_TOP: f32 = ARENA_HEIGHT / 2. - 100.;
+const FORMATION_SWAY_AMPLITUDE: f32 = 40.;
+const FORMATION_SWAY_SPEED: f32 = 1.2;
+//fraction of the distance to its slot a flyer covers per step
+const FORMATION_CATCH_UP: f32 = 0.1;
+const FORMATION_MAX_SPEED: f32 = 0.6;
+const FORMATION_DIVE_SPEED: f32 = 0.8;
+//seconds a flyer holds its slot before peeling off
+pub const FORMATION_DIVE_DELAY: (f32, f32) = (4., 12.);
+
+pub const KAMIKAZE_TURN_RATE: f32 = 2.5;
+
+pub const TURRET_HOLD_HEIGHT: (f32, f32) = (ARENA_HEIGHT / 8., ARENA_HEIGHT / 2. - 80.);
+pub const TURRET_FIRE_INTERVAL: f32 = 1.2;
+pub const TURRET_SHOTS: u32 = 6;
+//every that many shots a turret launches a homing missile instead
+const TURRET_MISSILE_EVERY: u32 = 3;
+//the last shot is a beam, the turret holds its position until it goes out
+const TURRET_BEAM_SECONDS: f32 = 1.;
+const TURRET_LEAVE_SPEED: f32 = 0.3;
+
+pub struct EnemyAiPlugin;
+
+impl Plugin for EnemyAiPlugin{
+    fn build(&self, app: &mut App) {
+        app
+        .insert_resource(Formation::default())
+        .add_system_set(
+            SystemSet::on_update(GameState::Playing)
+            .with_system(fly_formation.before(GameSystem::Movement))
+            .with_system(steer_kamikazes.before(GameSystem::Movement))
+            .with_system(operate_turrets.before(GameSystem::Movement))
+        );
+    }
+}
+
+pub fn formation_slot_position(slot: usize, sway_phase: f32) -> Vec2{
+    let column = (slot % FORMATION_COLUMNS) as f32 - (FORMATION_COLUMNS - 1) as f32 / 2.;
+    let row = (slot / FORMATION_COLUMNS) as f32;
+
+    Vec2::new(
+        column * FORMATION_SPACING.0 + sway_phase.sin() * FORMATION_SWAY_AMPLITUDE,
+        FORMATION_TOP - row * FORMATION_SPACING.1,
+    )
+}
+
+//sprites face down, turn them to face along a direction
+fn facing(direction: Vec2) -> Quat{
+    Quat::from_rotation_z(direction.y.atan2(direction.x) + FRAC_PI_2)
+}
+
+fn player_position(player_query: &Query<&Transform, (With<Player>, Without<Enemy>)>) -> Option<Vec2>{
+    player_query.get_single().ok().map(|transform| transform.translation.truncate())
+}
+
+fn fly_formation(time: ScaledTime, mut formation: ResMut<Formation>,
+    player_query: Query<&Transform, (With<Player>, Without<Enemy>)>,
+    mut query: Query<(&mut FormationFlyer, &Transform, &mut Velocity)>){
+
+    let delta = time.delta();
+    formation.sway_phase += delta.as_secs_f32() * FORMATION_SWAY_SPEED;
+    let target_player = player_position(&player_query);
+
+    for (mut flyer, transform, mut velocity) in query.iter_mut(){
+        if flyer.diving{
+            continue;
+        }
+
+        let position = transform.translation.truncate();
+        let offset = formation_slot_position(flyer.slot, formation.sway_phase) - position;
+        let step = (offset * FORMATION_CATCH_UP / (TIME_STEP * GAME_SPEED)).clamp_length_max(FORMATION_MAX_SPEED);
+        velocity.x = step.x;
+        velocity.y = step.y;
+
+        //the dive countdown starts once the flyer has settled into its slot
+        if offset.length() < FORMATION_SPACING.1 / 2.{
+            flyer.dive_timer.tick(delta);
+        }
+
+        if flyer.dive_timer.finished(){
+            let aim = target_player.map_or(Vec2::new(0., -1.), |player| player - position);
+            //always commit downwards so the flyer leaves the screen
+            let direction = Vec2::new(aim.x, aim.y.min(-1.)).normalize();
+            velocity.x = direction.x * FORMATION_DIVE_SPEED;
+            velocity.y = direction.y * FORMATION_DIVE_SPEED;
+            flyer.diving = true;
+        }
+    }
+}
+
+//homing stops once the kamikaze has passed the player, it then keeps going until culled
+fn steer_kamikazes(time: ScaledTime,
+    player_query: Query<&Transform, (With<Player>, Without<Enemy>)>,
+    mut query: Query<(&Kamikaze, &mut Transform, &mut Velocity), With<Enemy>>){
+
+    let dt = time.delta_seconds();
+    let target_player = player_position(&player_query);
+
+    for (kamikaze, mut transform, mut velocity) in query.iter_mut(){
+        let position = transform.translation.truncate();
+        let current = Vec2::new(velocity.x, velocity.y).try_normalize().unwrap_or(Vec2::new(0., -1.));
+
+        let direction = match target_player{
+            Some(player) if player.y < position.y => {
+                let desired = (player - position).try_normalize().unwrap_or(current);
+                let angle = current.angle_between(desired).clamp(-kamikaze.turn_rate * dt, kamikaze.turn_rate * dt);
+                (Quat::from_rotation_z(angle) * current.extend(0.)).truncate()
+            },
+            _ => current,
+        };
+
+        velocity.x = direction.x * kamikaze.speed;
+        velocity.y = direction.y * kamikaze.speed;
+        transform.rotation = facing(direction);
+    }
+}
+
+fn operate_turrets(mut commands: Commands, time: ScaledTime, mut bullet_pool: ResMut<BulletPool>, mut bullets_fired: EventWriter<BulletFired>,
+    player_query: Query<&Transform, (With<Player>, Without<Enemy>)>,
+    beam_query: Query<&LaserBeam>,
+    mut query: Query<(Entity, &mut Turret, &mut Transform, &mut Velocity, &ArchetypeName), With<Enemy>>){
+
+    let delta = time.delta();
+    let target_player = player_position(&player_query);
+
+    for (turret_entity, mut turret, mut transform, mut velocity, archetype) in query.iter_mut(){
+        let position = transform.translation.truncate();
+
+        if turret.shots_left == 0{
+            if beam_query.iter().any(|beam| beam.owner == turret_entity){
+                continue;
+            }
+            velocity.y = -TURRET_LEAVE_SPEED;
+            transform.rotation = Quat::IDENTITY;
+            continue;
+        }
+        if position.y > turret.hold_y{
+            continue;
+        }
+
+        velocity.x = 0.;
+        velocity.y = 0.;
+
+        let aim = target_player.map_or(Vec2::new(0., -1.), |player| player - position);
+        transform.rotation = facing(aim);
+
+        turret.fire_timer.tick(delta);
+        if turret.fire_timer.just_finished(){
+            let bullet = if turret.shots_left == 1{
+                fire_enemy_beam(&mut commands, turret_entity, aim, TURRET_BEAM_SECONDS)
+            }else if turret.shots_left % TURRET_MISSILE_EVERY == 0{
+                fire_missile(&mut commands, &mut bullet_pool, transform.translation, aim, MissileOwner::Enemy)
+            }else{
+                fire_enemy_bullet(&mut commands, &mut bullet_pool, transform.translation, aim)
+            };
+            commands.entity(bullet).insert(*archetype);
+            bullets_fired.send(BulletFired { from_player: false });
+            turret.shots_left -= 1;
+        }
+    }
+}
@@ -0,0 +1,56 @@
+use bevy::prelude::*;
+use crate::components::{BulletCancelled, BulletFired, EffectKind, EnemyCount, EnemyHit, EnemyKilled, ExplosionToSpawn, GameClock, GameState, PlayerDied, PlayerHit, PlayerState, Score, Wave, WaveCleared};
+use crate::enemy::archetype_named;
+
+pub struct GameEventsPlugin;
+
+impl Plugin for GameEventsPlugin{
+    fn build(&self, app: &mut App) {
+        app
+        .add_event::<EnemyKilled>()
+        .add_event::<EnemyHit>()
+        .add_event::<BulletCancelled>()
+        .add_event::<PlayerHit>()
+        .add_event::<PlayerDied>()
+        .add_event::<BulletFired>()
+        .add_event::<WaveCleared>()
+        .add_event::<ExplosionToSpawn>()
+        .add_system(count_kills)
+        .add_system(handle_player_death)
+        .add_system(explode_on_death);
+    }
+}
+
+fn count_kills(mut kills: EventReader<EnemyKilled>, mut score: ResMut<Score>, mut wave: ResMut<Wave>, mut enemy_count: ResMut<EnemyCount>, mut wave_cleared: EventWriter<WaveCleared>){
+    for _ in kills.iter(){
+        enemy_count.0 = enemy_count.0.saturating_sub(1);
+        score.0 += 1;
+
+        let number = wave.number;
+        if wave.enemy_killed(){
+            wave_cleared.send(WaveCleared { number });
+        }
+    }
+}
+
+//several things can kill the player in the same frame, it only counts as one death
+//the run is reset when the game over screen is left, so the final score stays on the hud
+fn handle_player_death(mut deaths: EventReader<PlayerDied>, clock: Res<GameClock>, mut player_state: ResMut<PlayerState>, mut state: ResMut<State<GameState>>){
+    if deaths.iter().count() == 0{
+        return;
+    }
+
+    if player_state.player_is_shot(clock.seconds_since_startup()){
+        let _ = state.set(GameState::GameOver);
+    }
+}
+
+fn explode_on_death(mut kills: EventReader<EnemyKilled>, mut deaths: EventReader<PlayerDied>, mut explosions: EventWriter<ExplosionToSpawn>){
+    for kill in kills.iter(){
+        let effect = kill.archetype.and_then(archetype_named).map_or(EffectKind::Enemy, |archetype| archetype.death_effect);
+        explosions.send(ExplosionToSpawn(kill.position, effect));
+    }
+    if let Some(death) = deaths.iter().last(){
+        explosions.send(ExplosionToSpawn(death.position, EffectKind::Player));
+    }
+}
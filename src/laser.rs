@@ -0,0 +1,361 @@
+use bevy::prelude::*;
+use bevy::ecs::system::{EntityCommands, SystemParam};
+use std::f32::consts::FRAC_PI_2;
+use crate::components::{ArchetypeName, Asteroid, BeamDuration, BulletFired, ComingFromEnemy, ComingFromPlayer, Enemy, EnemyHit, EnemyKilled, GameState, Health, LaserBeam, LaserEmitter, Player, PlayerDied, PlayerHit, PlayerIntent, SpriteSize};
+use crate::player::{ShieldedPlayer, VulnerablePlayer};
+use crate::{ARENA_HEIGHT, ARENA_WIDTH};
+use crate::utils::{GameSystem, ScaledTime};
+
+const LASER_WIDTH: f32 = 8.;
+const LASER_COLOR: Color = Color::rgba(0.5, 1.0, 0.9, 0.85);
+const ENEMY_LASER_COLOR: Color = Color::rgba(1.0, 0.4, 0.3, 0.85);
+const LASER_Z_LAYER: f32 = 2.;
+const LASER_MUZZLE_OFFSET: f32 = 30.;
+const PLAYER_LASER_DAMAGE_PER_SECOND: f32 = 3.;
+//seconds of continuous fire on a full charge
+pub const LASER_MAX_ENERGY: f32 = 2.;
+const LASER_RECHARGE_PER_SECOND: f32 = 0.4;
+const ENEMY_LASER_DAMAGE_PER_SECOND: f32 = 1.5;
+
+pub struct LaserPlugin;
+
+impl Plugin for LaserPlugin{
+    fn build(&self, app: &mut App) {
+        app
+        .add_system_set(
+            SystemSet::on_update(GameState::Playing)
+            .with_system(player_laser_input.after(GameSystem::Input))
+            .with_system(cast_beams.label(GameSystem::Beams).after(GameSystem::Movement))
+            .with_system(stretch_beams.after(GameSystem::Beams))
+            .with_system(expire_beams.after(GameSystem::Beams))
+        );
+    }
+}
+
+//distance along the ray to an axis aligned box, None when the ray misses it
+pub fn ray_aabb(origin: Vec2, direction: Vec2, center: Vec2, half_size: Vec2) -> Option<f32>{
+    let min = center - half_size;
+    let max = center + half_size;
+
+    let mut near = f32::NEG_INFINITY;
+    let mut far = f32::INFINITY;
+
+    for axis in 0..2{
+        if direction[axis].abs() < f32::EPSILON{
+            //parallel to this slab, only hits when already inside it
+            if origin[axis] < min[axis] || origin[axis] > max[axis]{
+                return None;
+            }
+            continue;
+        }
+
+        let t1 = (min[axis] - origin[axis]) / direction[axis];
+        let t2 = (max[axis] - origin[axis]) / direction[axis];
+        near = near.max(t1.min(t2));
+        far = far.min(t1.max(t2));
+    }
+
+    if near > far || far < 0.{
+        None
+    }else{
+        Some(near.max(0.))
+    }
+}
+
+//distance from a point inside the box to where the ray leaves it, a ray without a direction goes nowhere
+pub fn ray_exit(origin: Vec2, direction: Vec2, center: Vec2, half_size: Vec2) -> f32{
+    let mut exit = f32::INFINITY;
+
+    for axis in 0..2{
+        if direction[axis].abs() < f32::EPSILON{
+            continue;
+        }
+        let bound = center[axis] + half_size[axis] * direction[axis].signum();
+        exit = exit.min(((bound - origin[axis]) / direction[axis]).max(0.));
+    }
+
+    if exit.is_finite() { exit } else { 0. }
+}
+
+//closest box hit within max_length, direction has to be normalized
+pub fn raycast_first<T>(origin: Vec2, direction: Vec2, max_length: f32, boxes: impl Iterator<Item = (T, Vec2, Vec2)>) -> Option<(T, f32)>{
+    boxes
+        .filter_map(|(item, center, half_size)| {
+            ray_aabb(origin, direction, center, half_size)
+                .filter(|distance| *distance <= max_length)
+                .map(|distance| (item, distance))
+        })
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+}
+
+//the sprite starts without a length, stretch_beams sizes it once the beam has been cast
+fn spawn_beam<'w, 's, 'a>(commands: &'a mut Commands<'w, 's>, beam: LaserBeam, color: Color) -> EntityCommands<'w, 's, 'a>{
+    let mut entity = commands.spawn_bundle(SpriteBundle{
+        sprite: Sprite{
+            color,
+            custom_size: Some(Vec2::new(LASER_WIDTH, 0.)),
+            ..default()
+        },
+        ..default()
+    });
+    entity.insert(beam);
+    entity
+}
+
+//enemy beams are blocked by asteroids and burn the player instead of other enemies
+pub fn fire_enemy_beam(commands: &mut Commands, owner: Entity, direction: Vec2, seconds: f32) -> Entity{
+    spawn_beam(commands, LaserBeam::new(owner, direction, ENEMY_LASER_DAMAGE_PER_SECOND), ENEMY_LASER_COLOR)
+    .insert(ComingFromEnemy)
+    .insert(BeamDuration(Timer::from_seconds(seconds, false)))
+    .id()
+}
+
+fn player_laser_input(mut commands: Commands, intent: Res<PlayerIntent>, time: ScaledTime, mut shots_fired: EventWriter<BulletFired>,
+    mut player_query: Query<(Entity, &mut LaserEmitter), With<Player>>, beam_query: Query<(Entity, &LaserBeam), With<ComingFromPlayer>>){
+
+    let dt = time.delta_seconds();
+
+    for (player_entity, mut emitter) in player_query.iter_mut(){
+        let firing = intent.laser && emitter.energy > 0.;
+        let beam = beam_query.iter().find(|(_, beam)| beam.owner == player_entity).map(|(entity, _)| entity);
+
+        if firing{
+            emitter.energy = (emitter.energy - dt).max(0.);
+        }else{
+            emitter.energy = (emitter.energy + LASER_RECHARGE_PER_SECOND * dt).min(LASER_MAX_ENERGY);
+        }
+
+        match (firing, beam){
+            (true, None) => {
+                spawn_beam(&mut commands, LaserBeam::new(player_entity, Vec2::new(0., 1.), PLAYER_LASER_DAMAGE_PER_SECOND), LASER_COLOR)
+                .insert(ComingFromPlayer);
+                //switching the beam on counts as one shot
+                shots_fired.send(BulletFired { from_player: true });
+            },
+            (false, Some(beam)) => commands.entity(beam).despawn(),
+            _ => {}
+        }
+    }
+}
+
+type BeamTarget = (Entity, &'static Transform, &'static SpriteSize, &'static mut Health);
+type EnemyBeamTarget = (Entity, &'static Transform, &'static SpriteSize, &'static mut Health, Option<&'static ArchetypeName>);
+
+#[derive(SystemParam)]
+struct BeamTargets<'w, 's>{
+    enemies: Query<'w, 's, EnemyBeamTarget, With<Enemy>>,
+    asteroids: Query<'w, 's, BeamTarget, (With<Asteroid>, Without<Enemy>)>,
+    players: Query<'w, 's, ShieldedPlayer, (VulnerablePlayer, Without<Enemy>, Without<Asteroid>)>,
+}
+
+#[derive(SystemParam)]
+struct BeamEvents<'w, 's>{
+    kills: EventWriter<'w, 's, EnemyKilled>,
+    hits: EventWriter<'w, 's, EnemyHit>,
+    player_hits: EventWriter<'w, 's, PlayerHit>,
+    player_deaths: EventWriter<'w, 's, PlayerDied>,
+}
+
+//raycasts every beam from its owner and applies damage per second to the first thing it hits
+//beams end at the edge of the arena, so enemies still flying in from above can't be shot before they are on screen
+fn cast_beams(mut commands: Commands, time: ScaledTime, mut events: BeamEvents,
+    mut beam_query: Query<(Entity, &mut LaserBeam, Option<&ComingFromPlayer>)>,
+    owner_query: Query<(&Transform, Option<&ArchetypeName>), Without<LaserBeam>>,
+    mut targets: BeamTargets){
+
+    let dt = time.delta_seconds();
+
+    for (beam_entity, mut beam, from_player) in beam_query.iter_mut(){
+        let (owner, owner_archetype) = match owner_query.get(beam.owner){
+            Ok((owner, archetype)) => (owner.translation.truncate(), archetype.copied()),
+            Err(_) => {
+                commands.entity(beam_entity).despawn();
+                continue;
+            }
+        };
+        let from_player = from_player.is_some();
+
+        beam.origin = owner + beam.direction * LASER_MUZZLE_OFFSET;
+        beam.length = ray_exit(beam.origin, beam.direction, Vec2::ZERO, Vec2::new(ARENA_WIDTH, ARENA_HEIGHT) / 2.);
+
+        //asteroids block every beam, the player's hit enemies and the enemies' hit the player
+        let asteroids = targets.asteroids.iter()
+            .map(|(entity, transform, size, _)| (entity, transform.translation.truncate(), size.0 / 2.));
+        let enemies = targets.enemies.iter()
+            .filter(|_| from_player)
+            .map(|(entity, transform, size, _, _)| (entity, transform.translation.truncate(), size.0 * transform.scale.truncate() / 2.));
+        let players = targets.players.iter()
+            .filter(|_| !from_player)
+            .map(|(entity, transform, size, _, _)| (entity, transform.translation.truncate(), size.0 * transform.scale.truncate() / 2.));
+        let hit = raycast_first(beam.origin, beam.direction, beam.length, asteroids.chain(enemies).chain(players));
+
+        let previous_target = beam.target.take();
+        if let Some((hit_entity, distance)) = hit{
+            beam.length = distance;
+
+            //broken asteroids are handled by the asteroid plugin
+            if let Ok((_, _, _, mut asteroid_health)) = targets.asteroids.get_mut(hit_entity){
+                asteroid_health.0 -= beam.damage_per_second * dt;
+            }
+
+            if let Ok((enemy_entity, enemy_transform, _, mut enemy_health, archetype)) = targets.enemies.get_mut(hit_entity){
+                //burning the same enemy over several frames is one hit
+                if previous_target != Some(enemy_entity){
+                    events.hits.send(EnemyHit { position: enemy_transform.translation });
+                }
+                beam.target = Some(enemy_entity);
+
+                let was_alive = enemy_health.0 > 0.;
+                enemy_health.0 -= beam.damage_per_second * dt;
+
+                if was_alive && enemy_health.0 <= 0.{
+                    commands.entity(enemy_entity).despawn();
+                    events.kills.send(EnemyKilled { position: enemy_transform.translation, archetype: archetype.map(|archetype| archetype.0) });
+                }
+            }
+
+            //shields soak up the damage first, like they do for bullets
+            if let Ok((player_entity, player_transform, _, mut player_health, mut shield)) = targets.players.get_mut(hit_entity){
+                let damage = beam.damage_per_second * dt;
+                let depleted = player_health.take_damage(damage, shield.as_deref_mut());
+                events.player_hits.send(PlayerHit { damage, source: owner_archetype.map(|archetype| archetype.0) });
+
+                if depleted{
+                    commands.entity(player_entity).despawn_recursive();
+                    events.player_deaths.send(PlayerDied { position: player_transform.translation });
+                }
+            }
+        }
+    }
+}
+
+fn expire_beams(mut commands: Commands, time: ScaledTime, mut query: Query<(Entity, &mut BeamDuration)>){
+    for (entity, mut duration) in query.iter_mut(){
+        if duration.tick(time.delta()).finished(){
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+//the beam sprite is stretched from its origin to whatever stopped it
+fn stretch_beams(mut query: Query<(&LaserBeam, &mut Transform, &mut Sprite)>){
+    for (beam, mut transform, mut sprite) in query.iter_mut(){
+        let center = beam.origin + beam.direction * beam.length / 2.;
+        transform.translation = center.extend(LASER_Z_LAYER);
+        transform.rotation = Quat::from_rotation_z(beam.direction.y.atan2(beam.direction.x) - FRAC_PI_2);
+        sprite.custom_size = Some(Vec2::new(LASER_WIDTH, beam.length));
+    }
+}
+
+impl LaserEmitter{
+    pub fn full() -> Self{
+        Self { energy: LASER_MAX_ENERGY }
+    }
+
+    pub fn with_energy(energy: f32) -> Self{
+        Self { energy: energy.clamp(0., LASER_MAX_ENERGY) }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use bevy::ecs::event::Events;
+    use bevy::math::const_vec2;
+    use super::*;
+    use crate::components::{GameClock, TimeScale};
+    use crate::PLAYER_MAX_HEALTH;
+
+    const UP: Vec2 = Vec2::Y;
+    const HALF: Vec2 = const_vec2!([10., 10.]);
+
+    #[test]
+    fn hits_a_box_in_front(){
+        assert_eq!(ray_aabb(Vec2::ZERO, UP, Vec2::new(0., 100.), HALF), Some(90.));
+        assert_eq!(ray_aabb(Vec2::ZERO, UP, Vec2::new(8., 100.), HALF), Some(90.));
+
+        let diagonal = ray_aabb(Vec2::ZERO, Vec2::ONE.normalize(), Vec2::new(100., 100.), HALF).unwrap();
+        assert!((diagonal - 90. * 2f32.sqrt()).abs() < 1e-3);
+    }
+
+    #[test]
+    fn origin_inside_the_box_hits_at_zero(){
+        assert_eq!(ray_aabb(Vec2::new(3., 95.), UP, Vec2::new(0., 100.), HALF), Some(0.));
+    }
+
+    #[test]
+    fn parallel_ray_outside_the_slab_misses(){
+        assert_eq!(ray_aabb(Vec2::new(50., 0.), UP, Vec2::new(0., 100.), HALF), None);
+        assert_eq!(ray_aabb(Vec2::new(0., 50.), Vec2::new(1., 0.), Vec2::new(100., 100.), HALF), None);
+    }
+
+    #[test]
+    fn box_behind_the_origin_misses(){
+        assert_eq!(ray_aabb(Vec2::ZERO, UP, Vec2::new(0., -100.), HALF), None);
+    }
+
+    #[test]
+    fn hits_past_the_max_length_are_ignored(){
+        let boxes = || [("enemy", Vec2::new(0., 100.), HALF)].into_iter();
+
+        assert_eq!(raycast_first(Vec2::ZERO, UP, 50., boxes()), None);
+        assert_eq!(raycast_first(Vec2::ZERO, UP, 90., boxes()), Some(("enemy", 90.)));
+    }
+
+    #[test]
+    fn picks_the_nearest_box(){
+        let boxes = [
+            ("far", Vec2::new(0., 300.), HALF),
+            ("off to the side", Vec2::new(200., 50.), HALF),
+            ("near", Vec2::new(5., 100.), HALF),
+            ("behind", Vec2::new(0., -50.), HALF),
+            ("middle", Vec2::new(-5., 200.), HALF),
+        ];
+
+        assert_eq!(raycast_first(Vec2::ZERO, UP, 1000., boxes.into_iter()), Some(("near", 90.)));
+    }
+
+    #[test]
+    fn rays_end_at_the_arena_edge(){
+        let arena = Vec2::new(ARENA_WIDTH, ARENA_HEIGHT) / 2.;
+
+        assert_eq!(ray_exit(Vec2::new(0., -300.), UP, Vec2::ZERO, arena), 300. + ARENA_HEIGHT / 2.);
+        assert_eq!(ray_exit(Vec2::ZERO, Vec2::new(-1., 0.), Vec2::ZERO, arena), ARENA_WIDTH / 2.);
+        assert_eq!(ray_exit(Vec2::ZERO, Vec2::ZERO, Vec2::ZERO, arena), 0.);
+    }
+
+    fn beam_app() -> App{
+        let mut clock = GameClock::fixed(0.5);
+        clock.advance(0.);
+
+        let mut app = App::new();
+        app
+        .insert_resource(clock)
+        .insert_resource(TimeScale(1.))
+        .add_event::<EnemyKilled>()
+        .add_event::<EnemyHit>()
+        .add_event::<PlayerHit>()
+        .add_event::<PlayerDied>()
+        .add_system(cast_beams);
+        app
+    }
+
+    #[test]
+    fn enemy_beams_burn_the_player_and_pass_other_enemies(){
+        let mut app = beam_app();
+        let turret = app.world.spawn().insert_bundle((Transform::from_xyz(0., 200., 0.), ArchetypeName("turret"))).id();
+        let enemy = app.world.spawn().insert_bundle((Enemy, Transform::from_xyz(0., 100., 0.), SpriteSize(HALF * 2.), Health(1.))).id();
+        let player = app.world.spawn().insert_bundle((Player, Transform::from_xyz(0., -200., 0.), SpriteSize(HALF * 2.), Health(PLAYER_MAX_HEALTH))).id();
+        let beam = app.world.spawn().insert_bundle((LaserBeam::new(turret, -UP, ENEMY_LASER_DAMAGE_PER_SECOND), ComingFromEnemy)).id();
+
+        app.update();
+
+        //from the muzzle below the turret to the top of the player
+        assert_eq!(app.world.get::<LaserBeam>(beam).unwrap().length, 200. - LASER_MUZZLE_OFFSET + 190.);
+        assert_eq!(app.world.get::<Health>(enemy).unwrap().0, 1.);
+        assert_eq!(app.world.get::<Health>(player).unwrap().0, PLAYER_MAX_HEALTH - ENEMY_LASER_DAMAGE_PER_SECOND * 0.5);
+
+        let player_hits = app.world.resource::<Events<PlayerHit>>();
+        let sources: Vec<_> = player_hits.get_reader().iter(player_hits).map(|hit| hit.source).collect();
+        assert_eq!(sources, vec![Some("turret")]);
+    }
+}
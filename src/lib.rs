@@ -0,0 +1,239 @@
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use rand::{rngs::StdRng, SeedableRng};
+//the public surface: the plugins, the components, resources and events they work on,
+//and what the balance simulation needs to configure a run
+pub use components::*;
+pub use audio::AudioBackend;
+pub use enemy::{EnemyPlugin, ENEMY_ARCHETYPES};
+pub use player::PlayerPlugin;
+pub use settings::{Difficulty, GameSettings};
+pub use text::TextPlugin;
+use crate::arena::ArenaPlugin;
+use crate::assets::AssetLoadingPlugin;
+use crate::asteroid::AsteroidPlugin;
+use crate::audio::GameAudioPlugin;
+use crate::autopilot::AutopilotPlugin;
+use crate::background::BackgroundPlugin;
+use crate::bomb::BombPlugin;
+use crate::camera::CameraEffectsPlugin;
+use crate::difficulty::DifficultyPlugin;
+use crate::enemy_ai::EnemyAiPlugin;
+use crate::events::GameEventsPlugin;
+use crate::laser::LaserPlugin;
+use crate::menu::MenuPlugin;
+use crate::missile::MissilePlugin;
+use crate::particle::{ParticlePlugin, debris_emitter, spark_emitter};
+use crate::pickup::PickupPlugin;
+use crate::save::SaveGamePlugin;
+use crate::settings::SettingsPlugin;
+use crate::shield::ShieldPlugin;
+use crate::stats::StatsPlugin;
+use crate::utils::{GameSystem, advance_game_clock, animate_explosion, despawn_out_of_bounds, explosion_spawn, movable_system, pool_diagnostics, recycle_pools, setup_pool_diagnostics};
+#[cfg(feature = "debug-overlay")]
+use crate::debug_overlay::DebugOverlayPlugin;
+
+mod player;
+mod enemy;
+mod components;
+mod text;
+mod utils;
+pub mod pool;
+mod particle;
+mod background;
+mod audio;
+mod settings;
+mod camera;
+mod arena;
+mod assets;
+mod menu;
+mod difficulty;
+mod bomb;
+mod shield;
+mod pickup;
+mod enemy_ai;
+mod missile;
+mod laser;
+mod asteroid;
+mod save;
+mod stats;
+mod events;
+mod autopilot;
+#[cfg(feature = "debug-overlay")]
+mod debug_overlay;
+
+pub const BACKGROUND_COLOR: Color = Color::rgb(0.0, 0.0, 0.0);
+pub const ARENA_HEIGHT: f32 = 800.;
+pub const ARENA_WIDTH: f32 = 600.;
+pub const WINDOW_HEIGHT: f32 = ARENA_HEIGHT;
+pub const WINDOW_WIDTH: f32 = ARENA_WIDTH;
+
+pub const TIME_STEP: f32 = 1. / 60.;
+pub const GAME_SPEED: f32 = 500.;
+
+pub const MAXIMUM_NUM_OF_ENEMIES_IN_ARENA: u32 = 15u32;
+pub const ENEMIES_PER_WAVE: u32 = 20;
+pub const PLAYER_RESPAWN_DELAY: f64 = 2.;
+pub const PLAYER_MAX_HEALTH: f32 = 5.;
+pub const PLAYER_LIVES: u32 = 3;
+pub const PLAYER_BOMBS: u32 = 3;
+pub const PLAYER_SHIELD_CAPACITY: f32 = 2.;
+pub const PLAYER_MAX_SHIELD_CAPACITY: f32 = 5.;
+pub const SHIELD_RECHARGE_DELAY: f32 = 3.;
+
+pub const GAME_SEED_ENV: &str = "SPACE_SHOOTER_SEED";
+pub const NO_AUDIO_ENV: &str = "SPACE_SHOOTER_NO_AUDIO";
+
+//Everything the game needs on top of DefaultPlugins, so it can be embedded in another Bevy app
+pub struct SpaceShooterPlugin{
+    pub audio: AudioBackend,
+    pub seed: u64,
+}
+
+//options come from the environment, see the README
+impl Default for SpaceShooterPlugin{
+    fn default() -> Self {
+        Self { audio: audio_backend(), seed: game_seed() }
+    }
+}
+
+impl Plugin for SpaceShooterPlugin{
+    fn build(&self, app: &mut App) {
+        app
+        .add_state(GameState::Loading)
+        .insert_resource(EnemyCount(0))
+        .insert_resource(Wave::default())
+        .insert_resource(GameSeed(self.seed))
+        .insert_resource(GameRng(StdRng::seed_from_u64(self.seed)))
+        .init_resource::<GameClock>()
+        .insert_resource(BulletPool::default())
+        .insert_resource(ExplosionPool::default())
+
+        .add_startup_system(create_2d_camera)
+        .add_startup_system(setup_pool_diagnostics)
+        .add_plugin(SettingsPlugin)
+        .add_plugin(GameAudioPlugin {backend: self.audio})
+        .add_plugin(PlayerPlugin)
+        .add_plugin(EnemyPlugin)
+        .add_plugin(TextPlugin)
+        .add_plugin(ParticlePlugin)
+        .add_plugin(BackgroundPlugin)
+        .add_plugin(CameraEffectsPlugin)
+        .add_plugin(ArenaPlugin)
+        .add_plugin(AssetLoadingPlugin)
+        .add_plugin(MenuPlugin)
+        .add_plugin(DifficultyPlugin)
+        .add_plugin(BombPlugin)
+        .add_plugin(ShieldPlugin)
+        .add_plugin(PickupPlugin)
+        .add_plugin(EnemyAiPlugin)
+        .add_plugin(MissilePlugin)
+        .add_plugin(LaserPlugin)
+        .add_plugin(AsteroidPlugin)
+        .add_plugin(SaveGamePlugin)
+        .add_plugin(GameEventsPlugin)
+        .add_plugin(AutopilotPlugin)
+        .add_plugin(StatsPlugin)
+        .add_system_set(
+            SystemSet::on_update(GameState::Playing)
+            .with_system(movable_system.label(GameSystem::Movement))
+            .with_system(despawn_out_of_bounds.after(GameSystem::Movement))
+            .with_system(explosion_spawn)
+            .with_system(animate_explosion)
+        )
+        .add_system_to_stage(CoreStage::First, recycle_pools)
+        //Time is updated by an exclusive system at the start of this stage, before this one runs
+        .add_system_to_stage(CoreStage::First, advance_game_clock)
+        .add_system(pool_diagnostics);
+
+        #[cfg(feature = "debug-overlay")]
+        app.add_plugin(DebugOverlayPlugin);
+    }
+}
+
+//seed from the environment for reproducible runs, otherwise from the clock
+fn game_seed() -> u64{
+    std::env::var(GAME_SEED_ENV).ok()
+        .and_then(|seed| seed.parse().ok())
+        .unwrap_or_else(|| {
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map_or(0, |duration| duration.as_secs())
+        })
+}
+
+#[cfg(feature = "audio")]
+fn audio_backend() -> AudioBackend{
+    if std::env::var_os(NO_AUDIO_ENV).is_some(){
+        AudioBackend::Null
+    }else{
+        AudioBackend::Bevy
+    }
+}
+
+#[cfg(not(feature = "audio"))]
+fn audio_backend() -> AudioBackend{
+    AudioBackend::Null
+}
+
+fn create_2d_camera(mut commands: Commands, asset_server: Res<AssetServer>, mut texture_atlases: ResMut<Assets<TextureAtlas>>,){
+    commands.spawn_bundle(OrthographicCameraBundle::new_2d()).insert(MainCamera);
+    commands.spawn_bundle(UiCameraBundle::default());
+
+    let player_texture_handler: Handle<Image> = asset_server.load("images/player/player.png");
+    let texture_atlas = TextureAtlas::from_grid(player_texture_handler,Vec2::new(64., 64.), 1, 4);
+    let player_sprites = texture_atlases.add(texture_atlas);
+
+    let enemy_texture_handler: Handle<Image> = asset_server.load("images/enemy/enemy.png");
+    let texture_atlas = TextureAtlas::from_grid(enemy_texture_handler,Vec2::new(64., 64.), 1, 4);
+    let enemy_sprites = texture_atlases.add(texture_atlas);
+
+    let explosion_texture_handler: Handle<Image> = asset_server.load("images/explosion.png");
+    let texture_atlas = TextureAtlas::from_grid(explosion_texture_handler,Vec2::new(64., 64.), 1, 16);
+    let explosion_sprites = texture_atlases.add(texture_atlas);
+
+    let explosion_effect = |frame_duration: f32, scale: f32, tint: Color, z_layer: f32, particles: Option<ParticleEmitter>| EffectDefinition{
+        atlas: explosion_sprites.clone(),
+        frame_duration,
+        scale,
+        tint,
+        z_layer,
+        particles,
+        trauma: 0.,
+        hit_stop: 0.,
+    };
+
+    let mut effects = HashMap::default();
+    effects.insert(EffectKind::BulletHit, explosion_effect(0.05, 0.5, Color::WHITE, 20., Some(spark_emitter())));
+    effects.insert(EffectKind::Enemy, EffectDefinition{
+        trauma: 0.2,
+        ..explosion_effect(0.05, 1.5, Color::WHITE, 20., Some(debris_emitter()))
+    });
+    effects.insert(EffectKind::Player, EffectDefinition{
+        trauma: 0.6,
+        ..explosion_effect(0.07, 1.5, Color::rgb(0.6, 0.8, 1.0), 21., Some(debris_emitter()))
+    });
+    effects.insert(EffectKind::LargeEnemy, EffectDefinition{
+        trauma: 0.9,
+        hit_stop: 0.3,
+        ..explosion_effect(0.08, 3., Color::rgb(1.0, 0.7, 0.4), 22., Some(debris_emitter()))
+    });
+    effects.insert(EffectKind::Bomb, EffectDefinition{
+        trauma: 1.,
+        hit_stop: 0.15,
+        ..explosion_effect(0.06, 6., Color::rgb(1.0, 0.95, 0.8), 22., Some(spark_emitter()))
+    });
+    commands.insert_resource(GameEffects(effects));
+
+
+    let textures = GameTextures{
+        player: player_sprites,
+        enemy: enemy_sprites,
+        shield: asset_server.load("images/player/shield.png"),
+        asteroid: asset_server.load("images/asteroid.png"),
+    };
+
+    commands.insert_resource(textures);
+}
+
+
@@ -1,72 +1,34 @@
 use bevy::prelude::*;
-use player::*;
-use enemy::*;
-use components::*;
-use text::*;
-use utils::*;
-
-mod player;
-mod enemy;
-mod components;
-mod text;
-mod utils;
-
-const BACKGROUND_COLOR: Color = Color::rgb(0.0, 0.0, 0.0);
-const WINDOW_HEIGHT: f32 = 800.;
-const WINDOW_WIDTH: f32 = 600.;
-
-const TIME_STEP: f32 = 1. / 60.;
-const GAME_SPEED: f32 = 500.;
-
-const MAXIMUM_NUM_OF_ENEMIES_IN_ARENA: u32 = 15u32;
-const PLAYER_RESPAWN_DELAY: f64 = 2.;
+use space_shooter::*;
+#[cfg(feature = "dev-hot-reload")]
+use bevy::asset::AssetServerSettings;
+#[cfg(not(feature = "render"))]
+use bevy::{app::{ScheduleRunnerPlugin, ScheduleRunnerSettings}, render::settings::WgpuSettings, utils::Duration};
 
 fn main() {
 
-    App::new()
+    let mut app = App::new();
+    app
     .insert_resource(ClearColor(BACKGROUND_COLOR))
     .insert_resource(WindowDescriptor{
         title: "Spacey invader".to_string(),
         width: WINDOW_WIDTH, height: WINDOW_HEIGHT,
         ..default()
-    })
-    .insert_resource(EnemyCount(0))
-
-    .add_startup_system(create_2d_camera)
-    .add_plugins(DefaultPlugins)
-    .add_plugin(PlayerPlugin)
-    .add_plugin(EnemyPlugin)
-    .add_plugin(TextPlugin)
-    .add_system(explosion_spawn)
-    .add_system(animate_explosion)
-    .add_system(bevy::input::system::exit_on_esc_system)
-    .run();
-}
+    });
 
-fn create_2d_camera(mut commands: Commands, asset_server: Res<AssetServer>, mut texture_atlases: ResMut<Assets<TextureAtlas>>,){
-    commands.spawn_bundle(OrthographicCameraBundle::new_2d());
-    commands.spawn_bundle(UiCameraBundle::default());
+    //has to be in place before the asset plugin is built
+    #[cfg(feature = "dev-hot-reload")]
+    app.insert_resource(AssetServerSettings { watch_for_changes: true, ..default() });
 
-    let player_texture_handler: Handle<Image> = asset_server.load("images/player/player.png");
-    let texture_atlas = TextureAtlas::from_grid(player_texture_handler,Vec2::new(64., 64.), 1, 4);
-    let player_sprites = texture_atlases.add(texture_atlas);
+    //no window and no gpu, the schedule is run on a timer instead of by the window's event loop
+    #[cfg(not(feature = "render"))]
+    app
+    .insert_resource(WgpuSettings { backends: None, ..default() })
+    .insert_resource(ScheduleRunnerSettings::run_loop(Duration::from_secs_f32(TIME_STEP)))
+    .add_plugin(ScheduleRunnerPlugin);
 
-    let enemy_texture_handler: Handle<Image> = asset_server.load("images/enemy/enemy.png");
-    let texture_atlas = TextureAtlas::from_grid(enemy_texture_handler,Vec2::new(64., 64.), 1, 4);
-    let enemy_sprites = texture_atlases.add(texture_atlas);
-
-    let explosion_texture_handler: Handle<Image> = asset_server.load("images/explosion.png");
-    let texture_atlas = TextureAtlas::from_grid(explosion_texture_handler,Vec2::new(64., 64.), 1, 16);
-    let explosion_sprites = texture_atlases.add(texture_atlas);
-
-
-    let textures = GameTextures{
-        player: player_sprites,
-        enemy: enemy_sprites,
-        explosion: explosion_sprites
-    };
-
-    commands.insert_resource(textures);
+    app
+    .add_plugins(DefaultPlugins)
+    .add_plugin(SpaceShooterPlugin::default())
+    .run();
 }
-
-
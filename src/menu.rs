@@ -0,0 +1,354 @@
+use bevy::prelude::*;
+use bevy::app::AppExit;
+use crate::components::{Asteroid, Autopilot, Bullet, BulletPool, Enemy, EnemyCount, Explosion, ExplosionPool, GameState, LaserBeam, MenuItemText, MenuRoot, Particle, Pickup, Player, PlayerState, Score, Wave};
+use crate::save::SavedRun;
+use crate::settings::{Difficulty, GameSettings, KeyAction, is_bindable, key_name};
+
+const MENU_FONT: &str = "fonts/FiraSans-Regular.ttf";
+const MENU_TITLE_SIZE: f32 = 40.;
+const MENU_ITEM_SIZE: f32 = 24.;
+const MENU_ITEM_COLOR: Color = Color::WHITE;
+const MENU_SELECTED_COLOR: Color = Color::GOLD;
+const MENU_BACKGROUND_COLOR: Color = Color::rgba(0.0, 0.0, 0.0, 0.75);
+const VOLUME_STEP: f32 = 0.1;
+
+pub struct MenuPlugin;
+
+impl Plugin for MenuPlugin{
+    fn build(&self, app: &mut App) {
+        app
+        .insert_resource(MenuSelection::default())
+        .add_system_set(SystemSet::on_enter(GameState::MainMenu).with_system(reset_run).with_system(release_pooled).with_system(reset_run_state).with_system(spawn_menu))
+        .add_system_set(SystemSet::on_resume(GameState::MainMenu).with_system(spawn_menu))
+        .add_system_set(SystemSet::on_pause(GameState::MainMenu).with_system(despawn_menu))
+        .add_system_set(SystemSet::on_exit(GameState::MainMenu).with_system(despawn_menu))
+        .add_system_set(SystemSet::on_update(GameState::MainMenu).with_system(menu_input).with_system(update_menu_text))
+
+        .add_system_set(SystemSet::on_enter(GameState::Paused).with_system(spawn_menu))
+        .add_system_set(SystemSet::on_resume(GameState::Paused).with_system(spawn_menu))
+        .add_system_set(SystemSet::on_pause(GameState::Paused).with_system(despawn_menu))
+        .add_system_set(SystemSet::on_exit(GameState::Paused).with_system(despawn_menu))
+        .add_system_set(SystemSet::on_update(GameState::Paused).with_system(menu_input).with_system(update_menu_text))
+
+        .add_system_set(SystemSet::on_enter(GameState::Options).with_system(spawn_menu))
+        .add_system_set(SystemSet::on_exit(GameState::Options).with_system(despawn_menu))
+        .add_system_set(SystemSet::on_update(GameState::Options).with_system(menu_input).with_system(update_menu_text))
+
+        .add_system_set(SystemSet::on_enter(GameState::GameOver).with_system(spawn_menu))
+        .add_system_set(SystemSet::on_exit(GameState::GameOver).with_system(despawn_menu).with_system(reset_run).with_system(release_pooled).with_system(reset_run_state))
+        .add_system_set(SystemSet::on_update(GameState::GameOver).with_system(menu_input).with_system(update_menu_text))
+
+        .add_system_set(SystemSet::on_update(GameState::Playing).with_system(pause_game));
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum MenuItem{
+    Continue,
+    Play,
+    Resume,
+    Options,
+    MainMenu,
+    SaveAndQuit,
+    Quit,
+    Back,
+    Fullscreen,
+    Vsync,
+    ShowFps,
+    CameraEffects,
+    MasterVolume,
+    MusicVolume,
+    SfxVolume,
+    Difficulty,
+    AdaptiveDifficulty,
+    Bind(KeyAction),
+}
+
+#[derive(Default)]
+pub struct MenuSelection{
+    index: usize,
+    //the binding waiting for the next key press
+    rebinding: Option<KeyAction>,
+}
+
+fn menu_title(state: &GameState) -> &'static str{
+    match state{
+        GameState::Paused => "Paused",
+        GameState::Options => "Options",
+        GameState::GameOver => "Game over",
+        _ => "Spacey invader",
+    }
+}
+
+//continue is only offered when there is a saved run
+fn menu_items(state: &GameState, saved_run: &SavedRun) -> &'static [MenuItem]{
+    match state{
+        GameState::MainMenu if saved_run.snapshot.is_some() => &[MenuItem::Continue, MenuItem::Play, MenuItem::Options, MenuItem::Quit],
+        GameState::MainMenu => &[MenuItem::Play, MenuItem::Options, MenuItem::Quit],
+        GameState::GameOver => &[MenuItem::Play, MenuItem::MainMenu],
+        GameState::Paused => &[MenuItem::Resume, MenuItem::Options, MenuItem::SaveAndQuit, MenuItem::MainMenu],
+        GameState::Options => &[
+            MenuItem::Fullscreen,
+            MenuItem::Vsync,
+            MenuItem::ShowFps,
+            MenuItem::CameraEffects,
+            MenuItem::MasterVolume,
+            MenuItem::MusicVolume,
+            MenuItem::SfxVolume,
+            MenuItem::Difficulty,
+            MenuItem::AdaptiveDifficulty,
+            MenuItem::Bind(KeyAction::MoveLeft),
+            MenuItem::Bind(KeyAction::MoveRight),
+            MenuItem::Bind(KeyAction::Fire),
+            MenuItem::Bind(KeyAction::Missile),
+            MenuItem::Bind(KeyAction::Laser),
+            MenuItem::Bind(KeyAction::Bomb),
+            MenuItem::Back,
+        ],
+        _ => &[],
+    }
+}
+
+fn menu_label(item: MenuItem, settings: &GameSettings, selection: &MenuSelection) -> String{
+    let on_off = |enabled: bool| if enabled { "On" } else { "Off" };
+    let percent = |volume: f32| format!("{:.0}%", volume * 100.);
+
+    match item{
+        MenuItem::Continue => "Continue".to_string(),
+        MenuItem::Play => "Play".to_string(),
+        MenuItem::Resume => "Resume".to_string(),
+        MenuItem::Options => "Options".to_string(),
+        MenuItem::MainMenu => "Main menu".to_string(),
+        MenuItem::SaveAndQuit => "Save and quit".to_string(),
+        MenuItem::Quit => "Quit".to_string(),
+        MenuItem::Back => "Back".to_string(),
+        MenuItem::Fullscreen => format!("Fullscreen: {}", on_off(settings.fullscreen)),
+        MenuItem::Vsync => format!("Vsync: {}", on_off(settings.vsync)),
+        MenuItem::ShowFps => format!("Show FPS: {}", on_off(settings.show_fps)),
+        MenuItem::CameraEffects => format!("Screen shake: {}", on_off(settings.camera_effects)),
+        MenuItem::MasterVolume => format!("Master volume: {}", percent(settings.master_volume)),
+        MenuItem::MusicVolume => format!("Music volume: {}", percent(settings.music_volume)),
+        MenuItem::SfxVolume => format!("Effects volume: {}", percent(settings.sfx_volume)),
+        MenuItem::Difficulty => format!("Difficulty: {}", settings.difficulty.name()),
+        MenuItem::AdaptiveDifficulty => format!("Adaptive difficulty: {}", on_off(settings.adaptive_difficulty)),
+        MenuItem::Bind(action) if selection.rebinding == Some(action) => format!("{}: press a key", action.name()),
+        MenuItem::Bind(action) => format!("{}: {}", action.name(), key_name(settings.key_bindings.get(action))),
+    }
+}
+
+//left/right on a setting, -1 or 1
+fn adjust_setting(item: MenuItem, direction: f32, settings: &mut GameSettings){
+    let step_volume = |volume: &mut f32| *volume = ((*volume + direction * VOLUME_STEP) * 10.).round() / 10.;
+
+    match item{
+        MenuItem::Fullscreen => settings.fullscreen = !settings.fullscreen,
+        MenuItem::Vsync => settings.vsync = !settings.vsync,
+        MenuItem::ShowFps => settings.show_fps = !settings.show_fps,
+        MenuItem::CameraEffects => settings.camera_effects = !settings.camera_effects,
+        MenuItem::AdaptiveDifficulty => settings.adaptive_difficulty = !settings.adaptive_difficulty,
+        MenuItem::MasterVolume => step_volume(&mut settings.master_volume),
+        MenuItem::MusicVolume => step_volume(&mut settings.music_volume),
+        MenuItem::SfxVolume => step_volume(&mut settings.sfx_volume),
+        MenuItem::Difficulty => {
+            let count = Difficulty::ALL.len() as i32;
+            let index = Difficulty::ALL.iter().position(|difficulty| *difficulty == settings.difficulty).unwrap_or(0) as i32;
+            settings.difficulty = Difficulty::ALL[(index + direction as i32).rem_euclid(count) as usize];
+        },
+        _ => {}
+    }
+
+    settings.master_volume = settings.master_volume.clamp(0., 1.);
+    settings.music_volume = settings.music_volume.clamp(0., 1.);
+    settings.sfx_volume = settings.sfx_volume.clamp(0., 1.);
+}
+
+fn menu_input(mut input: ResMut<Input<KeyCode>>, mut state: ResMut<State<GameState>>, mut selection: ResMut<MenuSelection>,
+    mut settings: ResMut<GameSettings>, mut saved_run: ResMut<SavedRun>, mut exit: EventWriter<AppExit>){
+
+    let items = menu_items(state.current(), &saved_run);
+    if items.is_empty(){
+        return;
+    }
+
+    if let Some(action) = selection.rebinding{
+        let pressed = input.get_just_pressed().copied().find(|key| *key == KeyCode::Escape || is_bindable(*key));
+        if let Some(key) = pressed{
+            if key != KeyCode::Escape{
+                settings.key_bindings.set(action, key);
+            }
+            selection.rebinding = None;
+            input.reset(key);
+        }
+        return;
+    }
+
+    let count = items.len();
+    selection.index = selection.index.min(count - 1);
+    let item = items[selection.index];
+
+    if input.just_pressed(KeyCode::Down){
+        selection.index = (selection.index + 1) % count;
+    }
+    if input.just_pressed(KeyCode::Up){
+        selection.index = (selection.index + count - 1) % count;
+    }
+    if input.just_pressed(KeyCode::Left){
+        adjust_setting(item, -1., &mut settings);
+    }
+    if input.just_pressed(KeyCode::Right){
+        adjust_setting(item, 1., &mut settings);
+    }
+
+    //escape backs out of the current menu, the main menu has nothing to back out to
+    if input.just_pressed(KeyCode::Escape){
+        input.reset(KeyCode::Escape);
+        if *state.current() != GameState::MainMenu{
+            selection.index = 0;
+            let _ = state.pop();
+        }
+        return;
+    }
+
+    if !input.just_pressed(KeyCode::Return){
+        return;
+    }
+    input.reset(KeyCode::Return);
+
+    let next_index = selection.index;
+    selection.index = 0;
+
+    match item{
+        MenuItem::Continue => {
+            saved_run.resume_requested = true;
+            let _ = state.set(GameState::Playing);
+        },
+        MenuItem::Play => { let _ = state.set(GameState::Playing); },
+        MenuItem::Resume | MenuItem::Back => { let _ = state.pop(); },
+        MenuItem::Options => { let _ = state.push(GameState::Options); },
+        MenuItem::MainMenu => { let _ = state.replace(GameState::MainMenu); },
+        MenuItem::SaveAndQuit => {
+            saved_run.save_requested = true;
+            let _ = state.replace(GameState::MainMenu);
+        },
+        MenuItem::Quit => exit.send(AppExit),
+        MenuItem::Bind(action) => {
+            selection.index = next_index;
+            selection.rebinding = Some(action);
+        },
+        _ => {
+            selection.index = next_index;
+            adjust_setting(item, 1., &mut settings);
+        },
+    }
+}
+
+//in attract mode Esc ends the demo instead
+fn pause_game(mut input: ResMut<Input<KeyCode>>, mut state: ResMut<State<GameState>>, autopilot: Res<Autopilot>){
+    if input.just_pressed(KeyCode::Escape) && !autopilot.attract_mode{
+        input.reset(KeyCode::Escape);
+        let _ = state.push(GameState::Paused);
+    }
+}
+
+fn spawn_menu(mut commands: Commands, asset_server: Res<AssetServer>, state: Res<State<GameState>>, settings: Res<GameSettings>, selection: Res<MenuSelection>, saved_run: Res<SavedRun>){
+    let font = asset_server.load(MENU_FONT);
+    let current = state.current();
+
+    commands
+    .spawn_bundle(NodeBundle{
+        style: Style{
+            size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+            position_type: PositionType::Absolute,
+            flex_direction: FlexDirection::ColumnReverse,
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..default()
+        },
+        color: UiColor(MENU_BACKGROUND_COLOR),
+        ..default()
+    })
+    .insert(MenuRoot)
+    .with_children(|parent| {
+        parent.spawn_bundle(TextBundle{
+            style: Style{
+                margin: Rect { bottom: Val::Px(20.), ..default() },
+                ..default()
+            },
+            text: Text::with_section(
+                menu_title(current),
+                TextStyle { font_size: MENU_TITLE_SIZE, color: MENU_SELECTED_COLOR, font: font.clone() },
+                default()
+            ),
+            ..default()
+        });
+
+        for (index, item) in menu_items(current, &saved_run).iter().enumerate(){
+            parent
+            .spawn_bundle(TextBundle{
+                style: Style{
+                    margin: Rect::all(Val::Px(4.)),
+                    ..default()
+                },
+                text: Text::with_section(
+                    menu_label(*item, &settings, &selection),
+                    TextStyle { font_size: MENU_ITEM_SIZE, color: MENU_ITEM_COLOR, font: font.clone() },
+                    default()
+                ),
+                ..default()
+            })
+            .insert(MenuItemText(index));
+        }
+    });
+}
+
+fn despawn_menu(mut commands: Commands, query: Query<Entity, With<MenuRoot>>){
+    for entity in query.iter(){
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn update_menu_text(state: Res<State<GameState>>, settings: Res<GameSettings>, selection: Res<MenuSelection>, saved_run: Res<SavedRun>, mut query: Query<(&MenuItemText, &mut Text)>){
+    let items = menu_items(state.current(), &saved_run);
+
+    for (item_text, mut text) in query.iter_mut(){
+        if let Some(item) = items.get(item_text.0){
+            let label = menu_label(*item, &settings, &selection);
+            let color = if item_text.0 == selection.index { MENU_SELECTED_COLOR } else { MENU_ITEM_COLOR };
+
+            if text.sections[0].value != label{
+                text.sections[0].value = label;
+            }
+            if text.sections[0].style.color != color{
+                text.sections[0].style.color = color;
+            }
+        }
+    }
+}
+
+//back to a clean arena when a run ends through the menu or a game over
+fn reset_run(mut commands: Commands, player_query: Query<Entity, With<Player>>, enemy_query: Query<Entity, With<Enemy>>,
+    particle_query: Query<Entity, With<Particle>>, pickup_query: Query<Entity, With<Pickup>>, beam_query: Query<Entity, With<LaserBeam>>, asteroid_query: Query<Entity, With<Asteroid>>){
+
+    for entity in player_query.iter().chain(enemy_query.iter()).chain(particle_query.iter()).chain(pickup_query.iter()).chain(beam_query.iter()).chain(asteroid_query.iter()){
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+//pooled entities go back to their pool instead of being despawned
+fn release_pooled(mut commands: Commands, mut bullet_pool: ResMut<BulletPool>, mut explosion_pool: ResMut<ExplosionPool>,
+    bullet_query: Query<Entity, With<Bullet>>, explosion_query: Query<Entity, With<Explosion>>){
+
+    for entity in bullet_query.iter(){
+        bullet_pool.release(&mut commands, entity);
+    }
+    for entity in explosion_query.iter(){
+        explosion_pool.release(&mut commands, entity);
+    }
+}
+
+fn reset_run_state(mut score: ResMut<Score>, mut wave: ResMut<Wave>, mut enemy_count: ResMut<EnemyCount>, mut player_state: ResMut<PlayerState>){
+    score.0 = 0;
+    *wave = Wave::default();
+    enemy_count.0 = 0;
+    *player_state = PlayerState::default();
+}
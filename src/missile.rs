@@ -0,0 +1,172 @@
+use bevy::prelude::*;
+use bevy::ecs::system::SystemParam;
+use std::f32::consts::FRAC_PI_2;
+use crate::components::{Bullet, BulletFired, BulletPool, ComingFromEnemy, ComingFromPlayer, EffectKind, Enemy, ExplosionToSpawn, GameClock, GameState, Homing, MovableObject, Player, PlayerIntent, SpriteSize, Velocity};
+use crate::{ARENA_HEIGHT, ARENA_WIDTH};
+use crate::utils::{GameSystem, ScaledTime};
+
+const MISSILE_SIZE: (f32, f32) = (6., 16.);
+const PLAYER_MISSILE_COLOR: Color = Color::rgb(0.6, 0.9, 1.0);
+const ENEMY_MISSILE_COLOR: Color = Color::rgb(1.0, 0.7, 0.3);
+const PLAYER_MISSILE_SPEED: f32 = 0.9;
+const ENEMY_MISSILE_SPEED: f32 = 0.5;
+const PLAYER_MISSILE_TURN_RATE: f32 = 4.;
+const ENEMY_MISSILE_TURN_RATE: f32 = 1.5;
+const MISSILE_LIFETIME: f32 = 4.;
+const PLAYER_MISSILE_COOLDOWN: f64 = 0.8;
+
+pub struct MissilePlugin;
+
+impl Plugin for MissilePlugin{
+    fn build(&self, app: &mut App) {
+        app
+        .add_system_set(
+            SystemSet::on_update(GameState::Playing)
+            .with_system(player_fire_missile.after(GameSystem::Input))
+            .with_system(steer_missiles.before(GameSystem::Movement))
+        );
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MissileOwner{
+    Player,
+    Enemy,
+}
+
+//missiles are pooled bullets, so they go through the same collision systems
+pub fn fire_missile(commands: &mut Commands, bullet_pool: &mut BulletPool, position: Vec3, direction: Vec2, owner: MissileOwner) -> Entity{
+    let (color, speed, turn_rate) = match owner{
+        MissileOwner::Player => (PLAYER_MISSILE_COLOR, PLAYER_MISSILE_SPEED, PLAYER_MISSILE_TURN_RATE),
+        MissileOwner::Enemy => (ENEMY_MISSILE_COLOR, ENEMY_MISSILE_SPEED, ENEMY_MISSILE_TURN_RATE),
+    };
+    let direction = direction.try_normalize().unwrap_or(Vec2::new(0., 1.));
+    let velocity = direction * speed;
+
+    let missile = bullet_pool.acquire(commands);
+    commands.entity(missile).insert_bundle(SpriteBundle{
+        sprite: Sprite{
+            color,
+            custom_size: Some(Vec2::new(MISSILE_SIZE.0, MISSILE_SIZE.1)),
+            ..default()
+        },
+        transform: Transform{
+            translation: Vec3::new(position.x, position.y, 1.),
+            rotation: facing(direction),
+            ..default()
+        },
+        ..default()
+    })
+    .insert(SpriteSize::from(MISSILE_SIZE))
+    .insert(Bullet)
+    .insert(MovableObject {auto_despawn: true})
+    .insert(Velocity {x: velocity.x, y: velocity.y})
+    .insert(Homing{
+        target: None,
+        turn_rate,
+        speed,
+        lifetime: Timer::from_seconds(MISSILE_LIFETIME, false),
+    });
+
+    match owner{
+        MissileOwner::Player => commands.entity(missile).insert(ComingFromPlayer),
+        MissileOwner::Enemy => commands.entity(missile).insert(ComingFromEnemy),
+    };
+
+    missile
+}
+
+//the missile sprite points up
+fn facing(direction: Vec2) -> Quat{
+    Quat::from_rotation_z(direction.y.atan2(direction.x) - FRAC_PI_2)
+}
+
+//the player's missiles reload on the game clock
+#[derive(SystemParam)]
+struct MissileCooldown<'w, 's>{
+    clock: Res<'w, GameClock>,
+    last_fired: Local<'s, Option<f64>>,
+}
+
+impl<'w, 's> MissileCooldown<'w, 's>{
+    fn ready(&self) -> bool{
+        self.last_fired.is_none_or(|last| self.clock.seconds_since_startup() >= last + PLAYER_MISSILE_COOLDOWN)
+    }
+
+    fn start(&mut self){
+        *self.last_fired = Some(self.clock.seconds_since_startup());
+    }
+}
+
+fn player_fire_missile(mut commands: Commands, mut bullet_pool: ResMut<BulletPool>, mut bullets_fired: EventWriter<BulletFired>, intent: Res<PlayerIntent>,
+    mut cooldown: MissileCooldown, query: Query<&Transform, With<Player>>){
+
+    if !intent.missile || !cooldown.ready(){
+        return;
+    }
+
+    if let Ok(transform) = query.get_single(){
+        let position = transform.translation + Vec3::new(0., 40., 0.);
+        fire_missile(&mut commands, &mut bullet_pool, position, Vec2::new(0., 1.), MissileOwner::Player);
+        bullets_fired.send(BulletFired { from_player: true });
+        cooldown.start();
+    }
+}
+
+fn nearest(position: Vec2, candidates: impl Iterator<Item = (Entity, Vec2)>) -> Option<Entity>{
+    candidates
+        .min_by(|a, b| a.1.distance_squared(position).total_cmp(&b.1.distance_squared(position)))
+        .map(|(entity, _)| entity)
+}
+
+type SteeredMissile = (Entity, &'static mut Homing, &'static mut Velocity, &'static mut Transform, Option<&'static ComingFromPlayer>);
+type MissileTarget<T> = (With<T>, Without<Bullet>);
+
+//turn-rate limited steering, missiles that outlive their fuel burst
+fn steer_missiles(mut commands: Commands, mut bullet_pool: ResMut<BulletPool>, mut explosions: EventWriter<ExplosionToSpawn>, time: ScaledTime,
+    mut missile_query: Query<SteeredMissile, With<Bullet>>,
+    enemy_query: Query<(Entity, &Transform), MissileTarget<Enemy>>,
+    player_query: Query<(Entity, &Transform), MissileTarget<Player>>){
+
+    let delta = time.delta();
+    let dt = delta.as_secs_f32();
+    let half_arena = Vec2::new(ARENA_WIDTH, ARENA_HEIGHT) / 2.;
+
+    for (entity, mut homing, mut velocity, mut transform, from_player) in missile_query.iter_mut(){
+        homing.lifetime.tick(delta);
+        if homing.lifetime.finished(){
+            explosions.send(ExplosionToSpawn(transform.translation, EffectKind::BulletHit));
+            bullet_pool.release(&mut commands, entity);
+            continue;
+        }
+
+        let position = transform.translation.truncate();
+        let targets: Vec<(Entity, Vec2)> = if from_player.is_some(){
+            enemy_query.iter().map(|(target, transform)| (target, transform.translation.truncate())).collect()
+        }else{
+            player_query.iter().map(|(target, transform)| (target, transform.translation.truncate())).collect()
+        };
+        let target_position = |target: Entity| targets.iter().find(|(candidate, _)| *candidate == target).map(|(_, position)| *position);
+
+        //retarget when the current target is gone, only onto targets inside the arena
+        if homing.target.and_then(target_position).is_none(){
+            let visible = targets.iter().copied().filter(|(_, target)| target.abs().cmple(half_arena).all());
+            homing.target = nearest(position, visible);
+        }
+
+        let current = Vec2::new(velocity.x, velocity.y).try_normalize().unwrap_or(Vec2::new(0., 1.));
+        let direction = match homing.target.and_then(target_position){
+            Some(target) => {
+                let desired = (target - position).try_normalize().unwrap_or(current);
+                let max_turn = homing.turn_rate * dt;
+                let angle = current.angle_between(desired).clamp(-max_turn, max_turn);
+                (Quat::from_rotation_z(angle) * current.extend(0.)).truncate()
+            },
+            None => current,
+        };
+
+        velocity.x = direction.x * homing.speed;
+        velocity.y = direction.y * homing.speed;
+        transform.rotation = facing(direction);
+    }
+}
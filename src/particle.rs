@@ -0,0 +1,190 @@
+use std::f32::consts::{FRAC_PI_2, PI};
+use bevy::prelude::*;
+use rand::thread_rng;
+use crate::components::{Particle, ParticleEmitter};
+use crate::utils::ScaledTime;
+
+const PARTICLE_Z_LAYER: f32 = 15.;
+
+pub struct ParticlePlugin;
+
+impl Plugin for ParticlePlugin{
+    fn build(&self, app: &mut App) {
+        app
+        .add_system(emit_particles)
+        .add_system(update_particles);
+    }
+}
+
+pub fn engine_trail_emitter() -> ParticleEmitter{
+    ParticleEmitter{
+        spawn_rate: 60.,
+        burst_count: 0,
+        lifetime: 0.35,
+        offset: Vec2::new(0., -28.),
+        direction: -FRAC_PI_2,
+        spread: 0.25,
+        speed: (80., 140.),
+        start_color: Color::rgba(1.0, 0.85, 0.4, 0.9),
+        end_color: Color::rgba(1.0, 0.2, 0.0, 0.0),
+        start_size: 6.,
+        end_size: 1.,
+        accumulator: 0.,
+    }
+}
+
+pub fn debris_emitter() -> ParticleEmitter{
+    ParticleEmitter{
+        spawn_rate: 0.,
+        burst_count: 24,
+        lifetime: 0.8,
+        offset: Vec2::ZERO,
+        direction: 0.,
+        spread: PI,
+        speed: (60., 220.),
+        start_color: Color::rgba(0.75, 0.7, 0.65, 1.0),
+        end_color: Color::rgba(0.3, 0.25, 0.2, 0.0),
+        start_size: 5.,
+        end_size: 2.,
+        accumulator: 0.,
+    }
+}
+
+pub fn spark_emitter() -> ParticleEmitter{
+    ParticleEmitter{
+        spawn_rate: 0.,
+        burst_count: 10,
+        lifetime: 0.25,
+        offset: Vec2::ZERO,
+        direction: 0.,
+        spread: PI,
+        speed: (150., 320.),
+        start_color: Color::rgba(1.0, 1.0, 0.8, 1.0),
+        end_color: Color::rgba(1.0, 0.6, 0.1, 0.0),
+        start_size: 3.,
+        end_size: 1.,
+        accumulator: 0.,
+    }
+}
+
+pub fn spawn_particles(commands: &mut Commands, position: Vec2, particles: Vec<Particle>){
+    for particle in particles{
+        commands.spawn_bundle(SpriteBundle{
+            sprite: Sprite{
+                color: particle.color(),
+                custom_size: Some(Vec2::splat(particle.size())),
+                ..default()
+            },
+            transform: Transform::from_translation(position.extend(PARTICLE_Z_LAYER)),
+            ..default()
+        })
+        .insert(particle);
+    }
+}
+
+fn emit_particles(mut commands: Commands, time: ScaledTime, mut query: Query<(&mut ParticleEmitter, &Transform)>){
+    //purely cosmetic and emitted every frame, so it stays off the seeded game generator
+    let mut random_generator = thread_rng();
+
+    for (mut emitter, transform) in query.iter_mut(){
+        let particles = emitter.tick(time.delta_seconds(), &mut random_generator);
+        let position = transform.translation.truncate() + emitter.offset;
+        spawn_particles(&mut commands, position, particles);
+    }
+}
+
+fn update_particles(mut commands: Commands, time: ScaledTime, mut query: Query<(Entity, &mut Particle, &mut Transform, &mut Sprite)>){
+    let delta = time.delta_seconds();
+
+    for (entity, mut particle, mut transform, mut sprite) in query.iter_mut(){
+        if !particle.advance(delta){
+            commands.entity(entity).despawn();
+            continue;
+        }
+
+        transform.translation += (particle.velocity * delta).extend(0.);
+        sprite.color = particle.color();
+        sprite.custom_size = Some(Vec2::splat(particle.size()));
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use rand::{rngs::StdRng, SeedableRng};
+    use super::*;
+
+    fn random_generator() -> StdRng{
+        StdRng::seed_from_u64(7)
+    }
+
+    #[test]
+    fn emits_at_the_spawn_rate_across_ticks(){
+        let mut emitter = engine_trail_emitter();
+        let mut random_generator = random_generator();
+
+        //60 per second in uneven ticks, the fractions carry over
+        let emitted: usize = [0.004, 0.013, 0.25, 0.233, 0.5].iter()
+            .map(|delta| emitter.tick(*delta, &mut random_generator).len())
+            .sum();
+        assert_eq!(emitted, 60);
+        assert!(emitter.accumulator < 1.);
+    }
+
+    #[test]
+    fn burst_emitters_only_emit_on_burst(){
+        let mut emitter = debris_emitter();
+        let mut random_generator = random_generator();
+
+        assert!(emitter.tick(1., &mut random_generator).is_empty());
+        assert_eq!(emitter.burst(&mut random_generator).len(), emitter.burst_count as usize);
+    }
+
+    #[test]
+    fn particles_expire_after_their_lifetime(){
+        let mut particle = ParticleEmitter { burst_count: 1, ..engine_trail_emitter() }.burst(&mut random_generator()).remove(0);
+
+        assert!(particle.advance(0.2));
+        assert!(!particle.advance(0.2));
+    }
+
+    #[test]
+    fn velocity_stays_inside_the_cone(){
+        let emitter = ParticleEmitter { burst_count: 200, ..engine_trail_emitter() };
+
+        for particle in emitter.burst(&mut random_generator()){
+            let angle = particle.velocity.y.atan2(particle.velocity.x);
+            let speed = particle.velocity.length();
+
+            assert!((angle - emitter.direction).abs() <= emitter.spread + 1e-4);
+            assert!(speed >= emitter.speed.0 - 1e-3 && speed <= emitter.speed.1 + 1e-3);
+        }
+    }
+
+    fn assert_color(color: Color, expected: [f32; 4]){
+        let color = color.as_rgba_f32();
+        for i in 0..4{
+            assert!((color[i] - expected[i]).abs() < 1e-4, "{:?} != {:?}", color, expected);
+        }
+    }
+
+    #[test]
+    fn color_and_size_follow_the_age(){
+        let mut particle = spark_emitter().burst(&mut random_generator()).remove(0);
+        let (start, end) = (particle.start_color.as_rgba_f32(), particle.end_color.as_rgba_f32());
+        assert_color(particle.color(), start);
+        assert!((particle.size() - particle.start_size).abs() < 1e-4);
+
+        particle.advance(particle.lifetime / 2.);
+        let mut halfway = start;
+        for i in 0..4{
+            halfway[i] = (start[i] + end[i]) / 2.;
+        }
+        assert_color(particle.color(), halfway);
+        assert!((particle.size() - (particle.start_size + particle.end_size) / 2.).abs() < 1e-4);
+
+        //past the lifetime it stays at the end values
+        particle.advance(particle.lifetime);
+        assert_color(particle.color(), end);
+        assert!((particle.size() - particle.end_size).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,92 @@
+use bevy::prelude::*;
+use bevy::sprite::collide_aabb::collide;
+use rand::Rng;
+use crate::audio::{SoundEffect, SoundQueue};
+use crate::components::{EnemyKilled, GameRng, GameState, MovableObject, Pickup, PickupKind, Player, PlayerState, Shield, SpriteSize, Velocity};
+
+const PICKUP_DROP_CHANCE: f64 = 0.1;
+const PICKUP_SIZE: (f32, f32) = (16., 16.);
+const PICKUP_SPEED: f32 = 0.25;
+const PICKUP_Z_LAYER: f32 = 5.;
+const SHIELD_UPGRADE_AMOUNT: f32 = 1.;
+
+pub struct PickupPlugin;
+
+impl Plugin for PickupPlugin{
+    fn build(&self, app: &mut App) {
+        app
+        .add_system_set(
+            SystemSet::on_update(GameState::Playing)
+            .with_system(collect_pickups)
+        )
+        .add_system(drop_pickups);
+    }
+}
+
+impl PickupKind{
+    fn color(&self) -> Color{
+        match self{
+            PickupKind::ShieldUpgrade => Color::rgb(0.4, 0.8, 1.0),
+        }
+    }
+}
+
+//rolled for every destroyed enemy
+fn drop_pickups(mut commands: Commands, mut random_generator: ResMut<GameRng>, mut kills: EventReader<EnemyKilled>){
+    for kill in kills.iter(){
+        if random_generator.gen_bool(PICKUP_DROP_CHANCE){
+            spawn_pickup(&mut commands, PickupKind::ShieldUpgrade, kill.position);
+        }
+    }
+}
+
+pub fn spawn_pickup(commands: &mut Commands, kind: PickupKind, position: Vec3){
+    commands.spawn_bundle(SpriteBundle{
+        sprite: Sprite{
+            color: kind.color(),
+            custom_size: Some(Vec2::new(PICKUP_SIZE.0, PICKUP_SIZE.1)),
+            ..default()
+        },
+        transform: Transform{
+            translation: Vec3::new(position.x, position.y, PICKUP_Z_LAYER),
+            rotation: Quat::from_rotation_z(std::f32::consts::FRAC_PI_4),
+            ..default()
+        },
+        ..default()
+    })
+    .insert(Pickup(kind))
+    .insert(SpriteSize::from(PICKUP_SIZE))
+    .insert(MovableObject {auto_despawn: true})
+    .insert(Velocity {x: 0., y: -PICKUP_SPEED});
+}
+
+fn collect_pickups(mut commands: Commands, mut player_state: ResMut<PlayerState>, mut sounds: ResMut<SoundQueue>,
+    mut player_query: Query<(&Transform, &SpriteSize, Option<&mut Shield>), With<Player>>,
+    pickup_query: Query<(Entity, &Transform, &SpriteSize, &Pickup)>){
+
+    for (player_transform, player_size, mut shield) in player_query.iter_mut(){
+        let player_scale = Vec2::new(player_transform.scale.x, player_transform.scale.y);
+
+        for (pickup_entity, pickup_transform, pickup_size, pickup) in pickup_query.iter(){
+            let collision = collide(
+                player_transform.translation, player_size.0 * player_scale,
+                pickup_transform.translation, pickup_size.0);
+
+            if collision.is_none(){
+                continue;
+            }
+
+            match pickup.0{
+                PickupKind::ShieldUpgrade => {
+                    if let Some(shield) = shield.as_mut(){
+                        shield.upgrade(SHIELD_UPGRADE_AMOUNT);
+                        player_state.shield_capacity = shield.capacity;
+                    }
+                },
+            }
+
+            sounds.play(SoundEffect::Pickup);
+            commands.entity(pickup_entity).despawn();
+        }
+    }
+}
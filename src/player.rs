@@ -1,8 +1,12 @@
-use bevy::core::FixedTimestep;
 use bevy::prelude::*;
 use bevy::sprite::collide_aabb::collide;
 use bevy::utils::HashSet;
-use crate::*;
+use crate::components::{AnimationTimer, ArchetypeName, Autopilot, Bullet, BulletCancelled, BulletFired, BulletPool, ComingFromEnemy, ComingFromPlayer, EffectKind, Enemy, EnemyHit, EnemyKilled, ExplosionToSpawn, GameClock, GameState, GameTextures, Health, Invulnerable, LaserEmitter, MovableObject, Player, PlayerDied, PlayerHit, PlayerIntent, PlayerState, Score, Shield, ShieldBubble, SpriteSize, Velocity};
+use crate::{ARENA_HEIGHT, ARENA_WIDTH, PLAYER_MAX_HEALTH, PLAYER_RESPAWN_DELAY};
+use crate::particle::engine_trail_emitter;
+use crate::settings::GameSettings;
+use crate::shield::shield_bubble;
+use crate::utils::{GameSystem, animate_object, every, run_if_playing};
 
 
 const PLAYER_COLOR: Color = Color::rgb(0.5, 0.5, 0.5);
@@ -18,29 +22,33 @@ impl Plugin for PlayerPlugin{
         app
         .insert_resource(PlayerState::default())
         .insert_resource(Score(0))
+        .insert_resource(PlayerIntent::default())
         .add_system_set(
             SystemSet::new()
-            .with_run_criteria(FixedTimestep::step(0.5))
+            .with_run_criteria(every(0.5).chain(run_if_playing))
             .with_system(spawn_player)
         )
-        .add_system(animate_object)
-        .add_system(player_input_event)
-        .add_system(player_movement)
-        .add_system(player_shooting)
-        .add_system(player_bullet_movement)
-        .add_system(player_bullet_collision)
-        .add_system(player_to_enemy_collision)
-        .add_system(player_bullet_to_enemy_bullet_collision);
+        .add_system_set(
+            SystemSet::on_update(GameState::Playing)
+            .with_system(animate_object)
+            .with_system(read_keyboard_intent.label(GameSystem::Input))
+            .with_system(player_input_event.after(GameSystem::Input))
+            .with_system(player_movement.after(GameSystem::Movement))
+            .with_system(player_shooting.after(GameSystem::Input))
+            .with_system(player_bullet_collision)
+            .with_system(player_to_enemy_collision)
+            .with_system(player_bullet_to_enemy_bullet_collision)
+        );
 
     }
 }
 
-const PLAYER_BOTTOM_POSITION: f32 = (-WINDOW_HEIGHT/2.) + 100.;
+const PLAYER_BOTTOM_POSITION: f32 = (-ARENA_HEIGHT/2.) + 100.;
 
-fn spawn_player(mut commands: Commands, texture: Res<GameTextures>, mut player_state: ResMut<PlayerState>, time: Res<Time>){
+fn spawn_player(mut commands: Commands, texture: Res<GameTextures>, mut player_state: ResMut<PlayerState>, clock: Res<GameClock>){
 
 
-    let time_now = time.seconds_since_startup();
+    let time_now = clock.seconds_since_startup();
     let last_shot = player_state.last_shot;
 
     if !player_state.is_alive && (last_shot == -1. || time_now > last_shot + PLAYER_RESPAWN_DELAY){
@@ -63,8 +71,15 @@ fn spawn_player(mut commands: Commands, texture: Res<GameTextures>, mut player_s
         .insert(Player)
         .insert(SpriteSize::from(PLAYER_SIZE))
         .insert(Velocity {x: 0. , y: 0.})
-        .insert(Health(5f32)) //COMMON COMPONENT
-        .insert(AnimationTimer(Timer::from_seconds(0.1, true)));
+        .insert(MovableObject {auto_despawn: false})
+        .insert(Health(PLAYER_MAX_HEALTH)) //COMMON COMPONENT
+        .insert(engine_trail_emitter())
+        .insert(Shield::new(player_state.shield_capacity))
+        .insert(player_state.laser_energy.take().map_or_else(LaserEmitter::full, LaserEmitter::with_energy))
+        .insert(AnimationTimer(Timer::from_seconds(0.1, true)))
+        .with_children(|parent| {
+            parent.spawn_bundle(shield_bubble(texture.shield.clone())).insert(ShieldBubble);
+        });
 
         player_state.spawned();
     }
@@ -74,37 +89,49 @@ fn spawn_player(mut commands: Commands, texture: Res<GameTextures>, mut player_s
 
 
 
-fn player_input_event(input: Res<Input<KeyCode>>, mut query: Query<&mut Velocity, With<Player>>){
-    if let Ok(mut velocity) = query.get_single_mut(){
-        velocity.x = if input.pressed(KeyCode::Left){
-            -1.
-        } else if input.pressed(KeyCode::Right) {
-            1.
-        }else{
-            0.
-        }
+//the autopilot writes the intent itself while it is flying
+fn read_keyboard_intent(input: Res<Input<KeyCode>>, settings: Res<GameSettings>, autopilot: Res<Autopilot>, mut intent: ResMut<PlayerIntent>){
+    if autopilot.enabled{
+        return;
+    }
+
+    intent.move_x = if input.pressed(settings.key_bindings.move_left){
+        -1.
+    } else if input.pressed(settings.key_bindings.move_right) {
+        1.
+    }else{
+        0.
+    };
+    intent.fire = input.just_pressed(settings.key_bindings.fire);
+    intent.missile = input.just_pressed(settings.key_bindings.missile);
+    intent.laser = input.pressed(settings.key_bindings.laser);
+    intent.bomb = input.just_pressed(settings.key_bindings.bomb);
+}
 
+fn player_input_event(intent: Res<PlayerIntent>, mut query: Query<&mut Velocity, With<Player>>){
+    if let Ok(mut velocity) = query.get_single_mut(){
+        velocity.x = intent.move_x;
     }
 }
 
-fn player_movement(mut query: Query<(&Velocity, &mut Transform), With<Player>>){
+fn player_movement(mut query: Query<&mut Transform, With<Player>>){
 
-    for (velocity, mut transform) in query.iter_mut(){
+    for mut transform in query.iter_mut(){
 
         let translation = &mut transform.translation;
-        translation.x += velocity.x * TIME_STEP * GAME_SPEED;
-        translation.x = translation.x.min((WINDOW_WIDTH/2.) - PLAYER_SIZE.0).max((-WINDOW_WIDTH/2.) + PLAYER_SIZE.0);
+        translation.x = translation.x.min((ARENA_WIDTH/2.) - PLAYER_SIZE.0).max((-ARENA_WIDTH/2.) + PLAYER_SIZE.0);
     }
 }
 
-fn player_shooting(mut commands: Commands, input: Res<Input<KeyCode>>, query: Query<&Transform, With<Player>>){
+fn player_shooting(mut commands: Commands, mut bullet_pool: ResMut<BulletPool>, mut bullets_fired: EventWriter<BulletFired>, intent: Res<PlayerIntent>, query: Query<&Transform, With<Player>>){
 
     if let Ok(transform) = query.get_single(){  //SIGNLE QUERY, NOT MUTABLE ONLY READ
         let player_position_x = transform.translation.x;
         let player_position_y = transform.translation.y;
 
-        if input.just_pressed(KeyCode::Space){
-            commands.spawn_bundle(SpriteBundle{
+        if intent.fire{
+            let bullet = bullet_pool.acquire(&mut commands);
+            commands.entity(bullet).insert_bundle(SpriteBundle{
                 sprite: Sprite{
                     color: PLAYER_COLOR,
                     custom_size: Some(Vec2::new(PLAYER_BULLET_SIZE.0, PLAYER_BULLET_SIZE.1)),
@@ -123,37 +150,25 @@ fn player_shooting(mut commands: Commands, input: Res<Input<KeyCode>>, query: Qu
             .insert(MovableObject {auto_despawn: true})
             .insert(Velocity {x: 0. , y: 1.}); //COMMON COMPONENT
 
+            bullets_fired.send(BulletFired { from_player: true });
+
         }
     }
 
 }
 
-fn player_bullet_movement(mut commands: Commands, mut query: Query<(Entity,&Velocity, &mut Transform), With<Bullet>>){
-    let mut despawned_entitites: HashSet<Entity> = HashSet::new();
-
-    for(bullet_entity, velocity ,mut bullet_transform) in query.iter_mut(){
-        if despawned_entitites.contains(&bullet_entity){
-            continue;
-        }
-        
-        let bullet_position = &mut bullet_transform.translation;
-        bullet_position.y += velocity.y * TIME_STEP * GAME_SPEED;
-
-        if bullet_position.y > (WINDOW_HEIGHT - 400.) {
-            commands.entity(bullet_entity).despawn();
-            despawned_entitites.insert(bullet_entity);
-        }
-    }
-}
+//the player as collisions see it, damage goes through the shield first
+pub(crate) type ShieldedPlayer = (Entity, &'static Transform, &'static SpriteSize, &'static mut Health, Option<&'static mut Shield>);
+pub(crate) type VulnerablePlayer = (With<Player>, Without<Invulnerable>);
 
-fn player_to_enemy_collision(mut commands: Commands, mut score: ResMut<Score>, mut player_state: ResMut<PlayerState>, time: Res<Time>,mut player_query: Query<(Entity, &Transform, &SpriteSize, &mut Health),With<Player>>, enemy_query: Query<(Entity, &Transform, &SpriteSize), With<Enemy>>){
+fn player_to_enemy_collision(mut commands: Commands, mut explosions: EventWriter<ExplosionToSpawn>, mut player_hits: EventWriter<PlayerHit>, mut player_deaths: EventWriter<PlayerDied>, mut player_query: Query<ShieldedPlayer, VulnerablePlayer>, enemy_query: Query<(Entity, &Transform, &SpriteSize, Option<&ArchetypeName>), With<Enemy>>){
 
-    for(player_entity, player_transform, player_size,mut player_health) in player_query.iter_mut(){
+    for(player_entity, player_transform, player_size, mut player_health, mut shield) in player_query.iter_mut(){
 
         let player_scales: (f32, f32) = (player_transform.scale.x, player_transform.scale.y);
         let player_scale = Vec2::new(player_scales.0, player_scales.1);
 
-        for (enemy_entity, enemy_transform, enemy_size) in enemy_query.iter(){
+        for (enemy_entity, enemy_transform, enemy_size, archetype) in enemy_query.iter(){
             
             let enemy_scales: (f32, f32) = (enemy_transform.scale.x, enemy_transform.scale.y);
             let enemy_scale = Vec2::new(enemy_scales.0, enemy_scales.1);
@@ -164,20 +179,18 @@ fn player_to_enemy_collision(mut commands: Commands, mut score: ResMut<Score>, m
             );
 
             //colision logic
-            if let Some(_) = collision{
+            if collision.is_some(){
 
                 commands.entity(enemy_entity).despawn();
-                commands.spawn().insert(ExplosionToSpawn(enemy_transform.translation.clone(), 1.5f32));
+                explosions.send(ExplosionToSpawn(enemy_transform.translation, EffectKind::Enemy));
 
-                player_health.0 -= 1.;
-                if player_health.0 == 0.{
+                let depleted = player_health.take_damage(1., shield.as_deref_mut());
+                player_hits.send(PlayerHit { damage: 1., source: archetype.map(|archetype| archetype.0) });
+                if depleted{
 
                     player_health.0 = 0.;
-                    commands.spawn().insert(ExplosionToSpawn(player_transform.translation.clone(), 1.5f32));
-                    commands.entity(player_entity).despawn(); 
-
-                    score.0 = 0;
-                    player_state.player_is_shot(time.seconds_since_startup());
+                    commands.entity(player_entity).despawn_recursive(); 
+                    player_deaths.send(PlayerDied { position: player_transform.translation });
                 }  
             }
         }
@@ -185,9 +198,14 @@ fn player_to_enemy_collision(mut commands: Commands, mut score: ResMut<Score>, m
     }
 }
 
+type CollidingBullet = (Entity, &'static Transform, &'static SpriteSize);
+
 fn player_bullet_to_enemy_bullet_collision(mut commands: Commands, 
-    player_bullet_query: Query<(Entity, &Transform, &SpriteSize),(With<Bullet>, With<ComingFromPlayer>)>, 
-    enemy_bullet_query: Query<(Entity, &Transform, &SpriteSize), (With<Bullet>, With<ComingFromEnemy>)>){
+    mut bullet_pool: ResMut<BulletPool>,
+    mut explosions: EventWriter<ExplosionToSpawn>,
+    mut cancelled: EventWriter<BulletCancelled>,
+    player_bullet_query: Query<CollidingBullet, (With<Bullet>, With<ComingFromPlayer>)>, 
+    enemy_bullet_query: Query<CollidingBullet, (With<Bullet>, With<ComingFromEnemy>)>){
 
     let mut despawned_entitites: HashSet<Entity> = HashSet::new();
 
@@ -215,11 +233,12 @@ fn player_bullet_to_enemy_bullet_collision(mut commands: Commands,
             );
 
             //colision logic
-            if let Some(_) = collision{
-                commands.spawn().insert(ExplosionToSpawn(enemy_bullet_transform.translation.clone(), 0.5f32));
+            if collision.is_some(){
+                explosions.send(ExplosionToSpawn(enemy_bullet_transform.translation, EffectKind::BulletHit));
 
-                commands.entity(player_bullet_entity).despawn();
-                commands.entity(enemy_bullet_entity).despawn();
+                bullet_pool.release(&mut commands, player_bullet_entity);
+                bullet_pool.release(&mut commands, enemy_bullet_entity);
+                cancelled.send(BulletCancelled { position: enemy_bullet_transform.translation });
 
                 despawned_entitites.insert(player_bullet_entity);
                 despawned_entitites.insert(enemy_bullet_entity);
@@ -231,7 +250,9 @@ fn player_bullet_to_enemy_bullet_collision(mut commands: Commands,
 
   }
 
-fn player_bullet_collision(mut commands: Commands, mut score: ResMut<Score>, mut enemy_count: ResMut<EnemyCount>, player_query: Query<(Entity, &Transform, &SpriteSize), (With<Bullet>, With<ComingFromPlayer>)>,mut enemy_query: Query<(Entity, &Transform, &SpriteSize,&mut Health), With<Enemy>>){
+type ShotEnemy = (Entity, &'static Transform, &'static SpriteSize, &'static mut Health, Option<&'static ArchetypeName>);
+
+fn player_bullet_collision(mut commands: Commands, mut bullet_pool: ResMut<BulletPool>, mut hits: EventWriter<EnemyHit>, mut explosions: EventWriter<ExplosionToSpawn>, mut kills: EventWriter<EnemyKilled>, player_query: Query<CollidingBullet, (With<Bullet>, With<ComingFromPlayer>)>,mut enemy_query: Query<ShotEnemy, With<Enemy>>){
 
     let mut despawned_entitites: HashSet<Entity> = HashSet::new();
     //iterate through bullets
@@ -243,9 +264,10 @@ fn player_bullet_collision(mut commands: Commands, mut score: ResMut<Score>, mut
         let bullet_scales: (f32, f32) = (bullet_transform.scale.x, bullet_transform.scale.y);
         let bullet_scale = Vec2::new(bullet_scales.0, bullet_scales.1);
         //iterate thgourh enemies
-        for(enemy_entity, enemy_transform, enemy_size,mut enemy_health) in enemy_query.iter_mut(){
+        for(enemy_entity, enemy_transform, enemy_size,mut enemy_health, archetype) in enemy_query.iter_mut(){
 
-            if despawned_entitites.contains(&enemy_entity) ||despawned_entitites.contains(&bullet_entity){
+            //already destroyed this frame by something else, e.g. a laser
+            if despawned_entitites.contains(&enemy_entity) ||despawned_entitites.contains(&bullet_entity) || enemy_health.0 <= 0.{
                 continue;
             }
 
@@ -259,25 +281,23 @@ fn player_bullet_collision(mut commands: Commands, mut score: ResMut<Score>, mut
                 enemy_transform.translation, enemy_size.0 * enemy_scale);
 
             //colision logic
-            if let Some(_) = collision{
+            if collision.is_some(){
 
                 enemy_health.0 -= 1.;
-                if enemy_health.0 == 0.{
-
-                    commands.spawn().insert(ExplosionToSpawn(enemy_transform.translation.clone(), 1.5f32));
+                hits.send(EnemyHit { position: enemy_transform.translation });
+                if enemy_health.0 <= 0.{
 
                     commands.entity(enemy_entity).despawn();
                     despawned_entitites.insert(enemy_entity);
-                    enemy_count.0 -= 1;
-                    score.0 += 1;
+                    kills.send(EnemyKilled { position: enemy_transform.translation, archetype: archetype.map(|archetype| archetype.0) });
 
                     break;
 
                 }
 
-                commands.spawn().insert(ExplosionToSpawn(bullet_transform.translation.clone(), 0.5f32));
+                explosions.send(ExplosionToSpawn(bullet_transform.translation, EffectKind::BulletHit));
 
-                commands.entity(bullet_entity).despawn();
+                bullet_pool.release(&mut commands, bullet_entity);
                 despawned_entitites.insert(bullet_entity);
 
                 break;
@@ -0,0 +1,198 @@
+use std::marker::PhantomData;
+use bevy::prelude::*;
+use bevy::utils::HashSet;
+
+//Pool of reusable entities. Released entities are hidden and stripped of the
+//gameplay components in `B`, so queries filtering on those components skip them.
+//Callers insert their full bundle again after `acquire`.
+//Released entities are only handed out again after `recycle`, once per frame, so the
+//stripping queued by a release can't land on top of a reacquired entity's new bundle.
+pub struct EntityPool<B: Bundle>{
+    pub enabled: bool,
+    free: Vec<Entity>,
+    pending: Vec<Entity>,
+    released: HashSet<Entity>,
+    active: usize,
+    allocated: usize,
+    high_water_mark: usize,
+    phantom: PhantomData<fn() -> B>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolMetrics{
+    pub size: usize,
+    pub active: usize,
+    pub free: usize,
+    pub high_water_mark: usize,
+}
+
+impl<B: Bundle> Default for EntityPool<B>{
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            free: Vec::new(),
+            pending: Vec::new(),
+            released: HashSet::default(),
+            active: 0,
+            allocated: 0,
+            high_water_mark: 0,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<B: Bundle> EntityPool<B>{
+    pub fn acquire(&mut self, commands: &mut Commands) -> Entity{
+        let entity = match self.free.pop(){
+            Some(entity) => {
+                self.released.remove(&entity);
+                entity
+            },
+            None => {
+                self.allocated += 1;
+                commands.spawn().id()
+            }
+        };
+
+        self.active += 1;
+        self.high_water_mark = self.high_water_mark.max(self.active);
+        entity
+    }
+
+    //releasing the same entity twice in one frame is ignored, collision systems may hit it more than once
+    pub fn release(&mut self, commands: &mut Commands, entity: Entity){
+        if self.released.contains(&entity){
+            return;
+        }
+
+        self.active = self.active.saturating_sub(1);
+
+        if !self.enabled{
+            self.allocated = self.allocated.saturating_sub(1);
+            commands.entity(entity).despawn();
+            return;
+        }
+
+        commands.entity(entity)
+            .remove_bundle::<B>()
+            .insert(Visibility { is_visible: false });
+
+        self.released.insert(entity);
+        self.pending.push(entity);
+    }
+
+    //call between frames, after the commands of the releases have been applied
+    pub fn recycle(&mut self){
+        self.free.append(&mut self.pending);
+    }
+
+    pub fn metrics(&self) -> PoolMetrics{
+        PoolMetrics {
+            size: self.allocated,
+            active: self.active,
+            free: self.free.len() + self.pending.len(),
+            high_water_mark: self.high_water_mark,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use bevy::ecs::system::CommandQueue;
+    use super::*;
+
+    #[derive(Component)]
+    struct TestBullet;
+
+    type TestPool = EntityPool<(TestBullet,)>;
+
+    //runs one frame of pool calls against the world, like a system would
+    fn frame<R>(world: &mut World, pool: &mut TestPool, calls: impl FnOnce(&mut Commands, &mut TestPool) -> R) -> R{
+        let mut queue = CommandQueue::default();
+        let mut commands = Commands::new(&mut queue, world);
+        let result = calls(&mut commands, pool);
+        queue.apply(world);
+        pool.recycle();
+        result
+    }
+
+    fn fire(commands: &mut Commands, pool: &mut TestPool) -> Entity{
+        let bullet = pool.acquire(commands);
+        commands.entity(bullet).insert(TestBullet).insert(Visibility { is_visible: true });
+        bullet
+    }
+
+    #[test]
+    fn release_strips_and_hides_the_entity(){
+        let mut world = World::new();
+        let mut pool = TestPool::default();
+
+        let bullet = frame(&mut world, &mut pool, fire);
+        frame(&mut world, &mut pool, |commands, pool| pool.release(commands, bullet));
+
+        assert!(world.get::<TestBullet>(bullet).is_none());
+        assert!(!world.get::<Visibility>(bullet).unwrap().is_visible);
+    }
+
+    #[test]
+    fn releasing_twice_in_a_frame_counts_once(){
+        let mut world = World::new();
+        let mut pool = TestPool::default();
+
+        let bullets = frame(&mut world, &mut pool, |commands, pool| [fire(commands, pool), fire(commands, pool)]);
+        frame(&mut world, &mut pool, |commands, pool| {
+            pool.release(commands, bullets[0]);
+            pool.release(commands, bullets[0]);
+        });
+
+        let metrics = pool.metrics();
+        assert_eq!(metrics.active, 1);
+        assert_eq!(metrics.free, 1);
+    }
+
+    #[test]
+    fn released_entities_are_reused_from_the_next_frame(){
+        let mut world = World::new();
+        let mut pool = TestPool::default();
+
+        let bullet = frame(&mut world, &mut pool, fire);
+        let same_frame = frame(&mut world, &mut pool, |commands, pool| {
+            pool.release(commands, bullet);
+            fire(commands, pool)
+        });
+        assert_ne!(same_frame, bullet);
+
+        let next_frame = frame(&mut world, &mut pool, fire);
+        assert_eq!(next_frame, bullet);
+        assert!(world.get::<TestBullet>(next_frame).is_some());
+        assert_eq!(pool.metrics().size, 2);
+    }
+
+    #[test]
+    fn metrics_track_size_and_high_water_mark(){
+        let mut world = World::new();
+        let mut pool = TestPool::default();
+
+        let bullets = frame(&mut world, &mut pool, |commands, pool| (0..3).map(|_| fire(commands, pool)).collect::<Vec<_>>());
+        frame(&mut world, &mut pool, |commands, pool| {
+            for bullet in &bullets{
+                pool.release(commands, *bullet);
+            }
+        });
+        frame(&mut world, &mut pool, fire);
+
+        assert_eq!(pool.metrics(), PoolMetrics { size: 3, active: 1, free: 2, high_water_mark: 3 });
+    }
+
+    #[test]
+    fn disabled_pool_despawns_instead(){
+        let mut world = World::new();
+        let mut pool = TestPool { enabled: false, ..default() };
+
+        let bullet = frame(&mut world, &mut pool, fire);
+        frame(&mut world, &mut pool, |commands, pool| pool.release(commands, bullet));
+
+        assert!(world.get_entity(bullet).is_none());
+        assert_eq!(pool.metrics().size, 0);
+    }
+}
@@ -0,0 +1,444 @@
+use std::fs;
+use std::io;
+use std::marker::PhantomData;
+use bevy::prelude::*;
+use bevy::ecs::system::SystemParam;
+use bevy::window::WindowCloseRequested;
+use rand::{rngs::StdRng, SeedableRng};
+use crate::asteroid::spawn_asteroid;
+use crate::components::{ArchetypeName, Asteroid, AsteroidSize, Autopilot, CurrentDifficulty, Enemy, EnemyBehavior, EnemyCount, GameRng, GameSeed, GameState, GameTextures, Health, LaserEmitter, Player, PlayerState, Score, Velocity, Wave};
+use crate::enemy::{ENEMY_ARCHETYPES, archetype_named, spawn_enemy_entity};
+use crate::enemy_ai::FORMATION_SLOTS;
+use crate::laser::LASER_MAX_ENERGY;
+use crate::{PLAYER_BOMBS, PLAYER_LIVES, PLAYER_MAX_SHIELD_CAPACITY, PLAYER_SHIELD_CAPACITY};
+use crate::utils::key_values;
+
+pub const SAVE_FILE: &str = "savegame.cfg";
+//bumped whenever the meaning of an existing key changes, new keys alone don't need it
+const SAVE_VERSION: u32 = 2;
+
+pub struct SaveGamePlugin;
+
+impl Plugin for SaveGamePlugin{
+    fn build(&self, app: &mut App) {
+        app
+        .insert_resource(SavedRun::load(SAVE_FILE))
+        .add_system_set(SystemSet::on_exit(GameState::Paused).with_system(save_on_request))
+        .add_system_set(SystemSet::on_enter(GameState::Playing).with_system(resume_run))
+        .add_system(save_on_close);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SavedEntity{
+    Enemy { archetype: &'static str, position: Vec2, velocity: Vec2, health: f32 },
+    Asteroid { size: AsteroidSize, position: Vec2, velocity: Vec2, health: f32 },
+}
+
+//Everything needed to pick a run back up, stored as `key = value` lines like the settings.
+//The game has no weapon levels, the weapon state is the laser charge
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunSnapshot{
+    pub version: u32,
+    pub wave: u32,
+    pub wave_kills: u32,
+    pub score: u32,
+    pub lives: u32,
+    pub bombs: u32,
+    pub shield_capacity: f32,
+    pub laser_energy: f32,
+    pub seed: u64,
+    //the game generator continues from this seed after the save
+    pub rng_seed: u64,
+    pub entities: Vec<SavedEntity>,
+}
+
+impl Default for RunSnapshot{
+    fn default() -> Self {
+        Self {
+            version: SAVE_VERSION,
+            wave: 1,
+            wave_kills: 0,
+            score: 0,
+            lives: PLAYER_LIVES,
+            bombs: PLAYER_BOMBS,
+            shield_capacity: PLAYER_SHIELD_CAPACITY,
+            laser_energy: LASER_MAX_ENERGY,
+            seed: 0,
+            rng_seed: 0,
+            entities: Vec::new(),
+        }
+    }
+}
+
+impl AsteroidSize{
+    fn save_name(&self) -> &'static str{
+        match self{
+            AsteroidSize::Large => "large",
+            AsteroidSize::Medium => "medium",
+            AsteroidSize::Small => "small",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self>{
+        [AsteroidSize::Large, AsteroidSize::Medium, AsteroidSize::Small].into_iter().find(|size| size.save_name() == value)
+    }
+}
+
+fn parse_floats<const N: usize>(values: &[&str]) -> Option<[f32; N]>{
+    let mut floats = [0.; N];
+    if values.len() < N{
+        return None;
+    }
+    for (float, value) in floats.iter_mut().zip(values){
+        *float = value.parse().ok()?;
+    }
+    Some(floats)
+}
+
+impl SavedEntity{
+    fn serialize(&self) -> String{
+        match self{
+            SavedEntity::Enemy { archetype, position, velocity, health } =>
+                format!("enemy = {} {} {} {} {} {}", archetype, position.x, position.y, velocity.x, velocity.y, health),
+            SavedEntity::Asteroid { size, position, velocity, health } =>
+                format!("asteroid = {} {} {} {} {} {}", size.save_name(), position.x, position.y, velocity.x, velocity.y, health),
+        }
+    }
+
+    //extra trailing values written by newer versions are ignored
+    fn parse(key: &str, value: &str) -> Option<Self>{
+        let values: Vec<&str> = value.split_whitespace().collect();
+        match key{
+            "enemy" => {
+                //version 1 saves have no archetype, those enemies were all drifters
+                let (archetype, values) = match values.first().and_then(|name| archetype_named(name)){
+                    Some(archetype) => (archetype, &values[1..]),
+                    None => (&ENEMY_ARCHETYPES[0], &values[..]),
+                };
+                let [x, y, vx, vy, health] = parse_floats::<5>(values)?;
+                Some(SavedEntity::Enemy { archetype: archetype.name, position: Vec2::new(x, y), velocity: Vec2::new(vx, vy), health })
+            },
+            "asteroid" => {
+                let size = AsteroidSize::parse(values.first()?)?;
+                let [x, y, vx, vy, health] = parse_floats::<5>(&values[1..])?;
+                Some(SavedEntity::Asteroid { size, position: Vec2::new(x, y), velocity: Vec2::new(vx, vy), health })
+            },
+            _ => None,
+        }
+    }
+}
+
+impl RunSnapshot{
+    pub fn parse(contents: &str) -> Self{
+        let mut snapshot = Self::default();
+
+        for (key, value) in key_values(contents){
+            snapshot.set(key, value);
+        }
+
+        if snapshot.version > SAVE_VERSION{
+            warn!("save file version {} is newer than {}, unknown data is skipped", snapshot.version, SAVE_VERSION);
+        }
+
+        snapshot
+    }
+
+    pub fn serialize(&self) -> String{
+        let mut contents = format!(
+            "version = {}\nwave = {}\nwave_kills = {}\nscore = {}\nlives = {}\nbombs = {}\nshield_capacity = {}\nlaser_energy = {}\nseed = {}\nrng_seed = {}\n",
+            SAVE_VERSION, self.wave, self.wave_kills, self.score, self.lives, self.bombs, self.shield_capacity, self.laser_energy, self.seed, self.rng_seed
+        );
+
+        for entity in self.entities.iter(){
+            contents += &entity.serialize();
+            contents += "\n";
+        }
+
+        contents
+    }
+
+    //unknown keys are skipped so older builds can read newer saves
+    fn set(&mut self, key: &str, value: &str){
+        match key{
+            "version" => if let Ok(version) = value.parse() { self.version = version },
+            "wave" => if let Ok(wave) = value.parse::<u32>() { self.wave = wave.max(1) },
+            "wave_kills" => if let Ok(kills) = value.parse() { self.wave_kills = kills },
+            "score" => if let Ok(score) = value.parse() { self.score = score },
+            "lives" => if let Ok(lives) = value.parse::<u32>() { self.lives = lives.clamp(1, PLAYER_LIVES) },
+            "bombs" => if let Ok(bombs) = value.parse() { self.bombs = bombs },
+            "shield_capacity" => if let Ok(capacity) = value.parse::<f32>() { self.shield_capacity = capacity.clamp(0., PLAYER_MAX_SHIELD_CAPACITY) },
+            "laser_energy" => if let Ok(energy) = value.parse::<f32>() { self.laser_energy = energy.clamp(0., LASER_MAX_ENERGY) },
+            "seed" => if let Ok(seed) = value.parse() { self.seed = seed },
+            "rng_seed" => if let Ok(seed) = value.parse() { self.rng_seed = seed },
+            _ => {
+                if let Some(entity) = SavedEntity::parse(key, value){
+                    self.entities.push(entity);
+                }
+            }
+        }
+    }
+}
+
+//The run on disk, plus the menu's requests to write or resume it
+#[derive(Default)]
+pub struct SavedRun{
+    pub snapshot: Option<RunSnapshot>,
+    pub save_requested: bool,
+    pub resume_requested: bool,
+}
+
+impl SavedRun{
+    pub fn load(path: &str) -> Self{
+        let snapshot = fs::read_to_string(path).ok().map(|contents| RunSnapshot::parse(&contents));
+        Self { snapshot, ..default() }
+    }
+
+    fn store(&mut self, snapshot: RunSnapshot) -> io::Result<()>{
+        let result = fs::write(SAVE_FILE, snapshot.serialize());
+        self.snapshot = Some(snapshot);
+        result
+    }
+
+    //a resumed run is taken off disk, it is written again when the run is saved
+    fn take(&mut self) -> Option<RunSnapshot>{
+        if let Err(error) = fs::remove_file(SAVE_FILE){
+            if error.kind() != io::ErrorKind::NotFound{
+                warn!("failed to remove {}: {}", SAVE_FILE, error);
+            }
+        }
+        self.snapshot.take()
+    }
+}
+
+type SavedEnemy = (&'static Transform, &'static Velocity, &'static Health, &'static ArchetypeName);
+type SavedAsteroid = (&'static Asteroid, &'static Transform, &'static Velocity, &'static Health);
+
+//the resources a saved run restores
+#[derive(SystemParam)]
+pub struct RunProgress<'w, 's>{
+    score: ResMut<'w, Score>,
+    wave: ResMut<'w, Wave>,
+    player_state: ResMut<'w, PlayerState>,
+    seed: ResMut<'w, GameSeed>,
+    random_generator: ResMut<'w, GameRng>,
+    #[system_param(ignore)]
+    marker: PhantomData<&'s ()>,
+}
+
+//the entities a saved run restores
+#[derive(SystemParam)]
+pub struct RunEntities<'w, 's>{
+    enemies: Query<'w, 's, SavedEnemy, With<Enemy>>,
+    asteroids: Query<'w, 's, SavedAsteroid>,
+    lasers: Query<'w, 's, &'static LaserEmitter, With<Player>>,
+}
+
+fn take_snapshot(progress: &mut RunProgress, entities: &RunEntities) -> RunSnapshot{
+
+    let enemies = entities.enemies.iter().map(|(transform, velocity, health, archetype)| SavedEntity::Enemy{
+        archetype: archetype.0,
+        position: transform.translation.truncate(),
+        velocity: Vec2::new(velocity.x, velocity.y),
+        health: health.0,
+    });
+    let asteroids = entities.asteroids.iter().map(|(asteroid, transform, velocity, health)| SavedEntity::Asteroid{
+        size: asteroid.0,
+        position: transform.translation.truncate(),
+        velocity: Vec2::new(velocity.x, velocity.y),
+        health: health.0,
+    });
+
+    RunSnapshot{
+        version: SAVE_VERSION,
+        wave: progress.wave.number,
+        wave_kills: progress.wave.kills,
+        score: progress.score.0,
+        lives: progress.player_state.lives,
+        bombs: progress.player_state.bombs,
+        shield_capacity: progress.player_state.shield_capacity,
+        laser_energy: entities.lasers.iter().next().map_or(LASER_MAX_ENERGY, |laser| laser.energy),
+        seed: progress.seed.0,
+        rng_seed: progress.random_generator.checkpoint(),
+        entities: enemies.chain(asteroids).collect(),
+    }
+}
+
+fn write_snapshot(saved_run: &mut SavedRun, snapshot: RunSnapshot){
+    match saved_run.store(snapshot){
+        Ok(()) => info!("run saved to {}", SAVE_FILE),
+        Err(error) => warn!("failed to save the run to {}: {}", SAVE_FILE, error),
+    }
+}
+
+fn save_on_request(mut saved_run: ResMut<SavedRun>, mut progress: RunProgress, entities: RunEntities){
+    if saved_run.save_requested{
+        saved_run.save_requested = false;
+        let snapshot = take_snapshot(&mut progress, &entities);
+        write_snapshot(&mut saved_run, snapshot);
+    }
+}
+
+//closing the window in the middle of a run saves it instead of losing it
+fn save_on_close(mut close_events: EventReader<WindowCloseRequested>, state: Res<State<GameState>>, autopilot: Res<Autopilot>, mut saved_run: ResMut<SavedRun>,
+    mut progress: RunProgress, entities: RunEntities){
+
+    if close_events.iter().count() == 0{
+        return;
+    }
+
+    //the attract mode demo is not the player's run
+    let in_run = *state.current() == GameState::Playing || state.inactives().contains(&GameState::Playing);
+    if in_run && !autopilot.attract_mode{
+        let snapshot = take_snapshot(&mut progress, &entities);
+        write_snapshot(&mut saved_run, snapshot);
+    }
+}
+
+fn resume_run(mut commands: Commands, mut saved_run: ResMut<SavedRun>, texture: Res<GameTextures>, difficulty: Res<CurrentDifficulty>,
+    mut progress: RunProgress, mut enemy_count: ResMut<EnemyCount>){
+
+    if !saved_run.resume_requested{
+        return;
+    }
+    saved_run.resume_requested = false;
+
+    let snapshot = match saved_run.take(){
+        Some(snapshot) => snapshot,
+        None => return,
+    };
+
+    progress.score.0 = snapshot.score;
+    *progress.wave = Wave { number: snapshot.wave, kills: snapshot.wave_kills };
+    *progress.player_state = PlayerState{
+        lives: snapshot.lives,
+        bombs: snapshot.bombs,
+        shield_capacity: snapshot.shield_capacity,
+        laser_energy: Some(snapshot.laser_energy),
+        ..default()
+    };
+    progress.seed.0 = snapshot.seed;
+    progress.random_generator.0 = StdRng::seed_from_u64(snapshot.rng_seed);
+
+    //behaviour timers are not saved, formation flyers take the grid slots in order
+    let mut free_slots = 0..FORMATION_SLOTS;
+    for entity in snapshot.entities{
+        match entity{
+            SavedEntity::Enemy { archetype, position, velocity, health } => {
+                let mut archetype = archetype_named(archetype).unwrap_or(&ENEMY_ARCHETYPES[0]);
+                let mut slot = None;
+                if archetype.behavior == EnemyBehavior::Formation{
+                    slot = free_slots.next();
+                    if slot.is_none(){
+                        archetype = &ENEMY_ARCHETYPES[0];
+                    }
+                }
+
+                let enemy = spawn_enemy_entity(&mut commands, &texture, &difficulty, archetype, slot, position, &mut progress.random_generator.0);
+                commands.entity(enemy)
+                .insert(Velocity {x: velocity.x, y: velocity.y})
+                .insert(Health(health));
+                enemy_count.0 += 1;
+            },
+            SavedEntity::Asteroid { size, position, velocity, health } => {
+                let asteroid = spawn_asteroid(&mut commands, &texture, size, position, velocity, &mut progress.random_generator.0);
+                commands.entity(asteroid).insert(Health(health));
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use rand::Rng;
+
+    fn snapshot() -> RunSnapshot{
+        RunSnapshot{
+            wave: 4,
+            wave_kills: 7,
+            score: 123,
+            lives: 2,
+            bombs: 1,
+            shield_capacity: 3.5,
+            laser_energy: 0.75,
+            seed: 99,
+            rng_seed: 12345678901234,
+            entities: vec![
+                SavedEntity::Enemy { archetype: "turret", position: Vec2::new(10., 200.5), velocity: Vec2::new(0., -1.5), health: 2. },
+                SavedEntity::Enemy { archetype: "escort", position: Vec2::new(-40., 300.), velocity: Vec2::ZERO, health: 1. },
+                SavedEntity::Asteroid { size: AsteroidSize::Medium, position: Vec2::new(5., 6.), velocity: Vec2::new(0.25, -2.), health: 3. },
+            ],
+            ..default()
+        }
+    }
+
+    #[test]
+    fn snapshot_round_trips(){
+        let snapshot = snapshot();
+        assert_eq!(RunSnapshot::parse(&snapshot.serialize()), snapshot);
+    }
+
+    #[test]
+    fn unknown_keys_are_skipped(){
+        let contents = format!("{}difficulty_curve = steep\nboss = 1 2 3\n", snapshot().serialize());
+        assert_eq!(RunSnapshot::parse(&contents), snapshot());
+    }
+
+    #[test]
+    fn newer_versions_keep_the_known_keys(){
+        let contents = snapshot().serialize().replace(&format!("version = {}", SAVE_VERSION), "version = 9");
+        let parsed = RunSnapshot::parse(&contents);
+
+        assert_eq!(parsed.version, 9);
+        assert_eq!(RunSnapshot { version: SAVE_VERSION, ..parsed }, snapshot());
+    }
+
+    #[test]
+    fn extra_trailing_values_are_ignored(){
+        assert_eq!(
+            SavedEntity::parse("enemy", "kamikaze 1 2 3 4 0.5 spin 7"),
+            Some(SavedEntity::Enemy { archetype: "kamikaze", position: Vec2::new(1., 2.), velocity: Vec2::new(3., 4.), health: 0.5 })
+        );
+        assert_eq!(
+            SavedEntity::parse("asteroid", "small 1 2 3 4 1 extra"),
+            Some(SavedEntity::Asteroid { size: AsteroidSize::Small, position: Vec2::new(1., 2.), velocity: Vec2::new(3., 4.), health: 1. })
+        );
+    }
+
+    #[test]
+    fn version_one_enemies_are_drifters(){
+        assert_eq!(
+            SavedEntity::parse("enemy", "1 2 3 4 1"),
+            Some(SavedEntity::Enemy { archetype: "drifter", position: Vec2::new(1., 2.), velocity: Vec2::new(3., 4.), health: 1. })
+        );
+    }
+
+    #[test]
+    fn broken_entities_are_skipped(){
+        assert_eq!(SavedEntity::parse("enemy", "mothership 1 2 3 4 1"), None);
+        assert_eq!(SavedEntity::parse("enemy", "drifter 1 2"), None);
+        assert_eq!(SavedEntity::parse("asteroid", "huge 1 2 3 4 1"), None);
+        assert_eq!(SavedEntity::parse("satellite", "1 2 3 4 1"), None);
+    }
+
+    #[test]
+    fn values_are_clamped(){
+        let parsed = RunSnapshot::parse("lives = 40\nwave = 0\nshield_capacity = -3\nlaser_energy = 100\n");
+        assert_eq!(parsed.lives, PLAYER_LIVES);
+        assert_eq!(parsed.wave, 1);
+        assert_eq!(parsed.shield_capacity, 0.);
+        assert_eq!(parsed.laser_energy, LASER_MAX_ENERGY);
+    }
+
+    #[test]
+    fn checkpoint_continues_from_the_saved_seed(){
+        let mut random_generator = GameRng(StdRng::seed_from_u64(1));
+        let rng_seed = random_generator.checkpoint();
+        let mut resumed = StdRng::seed_from_u64(rng_seed);
+
+        let continued: Vec<u32> = (0..8).map(|_| random_generator.gen()).collect();
+        let restored: Vec<u32> = (0..8).map(|_| resumed.gen()).collect();
+        assert_eq!(continued, restored);
+    }
+}
@@ -0,0 +1,311 @@
+use std::fs;
+use std::io;
+use bevy::prelude::*;
+use bevy::window::{PresentMode, WindowMode};
+use crate::components::FpsText;
+use crate::utils::key_values;
+
+pub const SETTINGS_FILE: &str = "settings.cfg";
+
+//keys that can be bound, also the lookup table for reading them back from the settings file
+const BINDABLE_KEYS: &[KeyCode] = &[
+    KeyCode::Left, KeyCode::Right, KeyCode::Up, KeyCode::Down,
+    KeyCode::Space, KeyCode::Return, KeyCode::Tab, KeyCode::Back,
+    KeyCode::LShift, KeyCode::RShift, KeyCode::LControl, KeyCode::RControl, KeyCode::LAlt, KeyCode::RAlt,
+    KeyCode::A, KeyCode::B, KeyCode::C, KeyCode::D, KeyCode::E, KeyCode::F, KeyCode::G, KeyCode::H,
+    KeyCode::I, KeyCode::J, KeyCode::K, KeyCode::L, KeyCode::M, KeyCode::N, KeyCode::O, KeyCode::P,
+    KeyCode::Q, KeyCode::R, KeyCode::S, KeyCode::T, KeyCode::U, KeyCode::V, KeyCode::W, KeyCode::X,
+    KeyCode::Y, KeyCode::Z,
+    KeyCode::Key0, KeyCode::Key1, KeyCode::Key2, KeyCode::Key3, KeyCode::Key4,
+    KeyCode::Key5, KeyCode::Key6, KeyCode::Key7, KeyCode::Key8, KeyCode::Key9,
+];
+
+pub struct SettingsPlugin;
+
+impl Plugin for SettingsPlugin{
+    fn build(&self, app: &mut App) {
+        app
+        .insert_resource(GameSettings::load(SETTINGS_FILE))
+        .add_system(apply_video_settings)
+        .add_system(save_settings);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Difficulty{
+    Easy,
+    Normal,
+    Hard,
+}
+
+impl Difficulty{
+    pub const ALL: [Difficulty; 3] = [Difficulty::Easy, Difficulty::Normal, Difficulty::Hard];
+
+    pub fn name(&self) -> &'static str{
+        match self{
+            Difficulty::Easy => "Easy",
+            Difficulty::Normal => "Normal",
+            Difficulty::Hard => "Hard",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self>{
+        Self::ALL.iter().copied().find(|difficulty| difficulty.name().eq_ignore_ascii_case(value))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KeyAction{
+    MoveLeft,
+    MoveRight,
+    Fire,
+    Missile,
+    Laser,
+    Bomb,
+}
+
+impl KeyAction{
+    pub const ALL: [KeyAction; 6] = [KeyAction::MoveLeft, KeyAction::MoveRight, KeyAction::Fire, KeyAction::Missile, KeyAction::Laser, KeyAction::Bomb];
+
+    pub fn name(&self) -> &'static str{
+        match self{
+            KeyAction::MoveLeft => "Move left",
+            KeyAction::MoveRight => "Move right",
+            KeyAction::Fire => "Fire",
+            KeyAction::Missile => "Missile",
+            KeyAction::Laser => "Laser",
+            KeyAction::Bomb => "Bomb",
+        }
+    }
+
+    fn key(&self) -> &'static str{
+        match self{
+            KeyAction::MoveLeft => "key_move_left",
+            KeyAction::MoveRight => "key_move_right",
+            KeyAction::Fire => "key_fire",
+            KeyAction::Missile => "key_missile",
+            KeyAction::Laser => "key_laser",
+            KeyAction::Bomb => "key_bomb",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyBindings{
+    pub move_left: KeyCode,
+    pub move_right: KeyCode,
+    pub fire: KeyCode,
+    pub missile: KeyCode,
+    pub laser: KeyCode,
+    pub bomb: KeyCode,
+}
+
+impl Default for KeyBindings{
+    fn default() -> Self {
+        Self { move_left: KeyCode::Left, move_right: KeyCode::Right, fire: KeyCode::Space, missile: KeyCode::X, laser: KeyCode::C, bomb: KeyCode::B }
+    }
+}
+
+impl KeyBindings{
+    pub fn get(&self, action: KeyAction) -> KeyCode{
+        match action{
+            KeyAction::MoveLeft => self.move_left,
+            KeyAction::MoveRight => self.move_right,
+            KeyAction::Fire => self.fire,
+            KeyAction::Missile => self.missile,
+            KeyAction::Laser => self.laser,
+            KeyAction::Bomb => self.bomb,
+        }
+    }
+
+    pub fn set(&mut self, action: KeyAction, key: KeyCode){
+        match action{
+            KeyAction::MoveLeft => self.move_left = key,
+            KeyAction::MoveRight => self.move_right = key,
+            KeyAction::Fire => self.fire = key,
+            KeyAction::Missile => self.missile = key,
+            KeyAction::Laser => self.laser = key,
+            KeyAction::Bomb => self.bomb = key,
+        }
+    }
+}
+
+pub fn is_bindable(key: KeyCode) -> bool{
+    BINDABLE_KEYS.contains(&key)
+}
+
+pub fn key_name(key: KeyCode) -> String{
+    format!("{:?}", key)
+}
+
+fn parse_key(value: &str) -> Option<KeyCode>{
+    BINDABLE_KEYS.iter().copied().find(|key| key_name(*key) == value)
+}
+
+//Options persisted between sessions as `key = value` lines, unknown keys are ignored
+#[derive(Debug, Clone, PartialEq)]
+pub struct GameSettings{
+    pub master_volume: f32,
+    pub music_volume: f32,
+    pub sfx_volume: f32,
+    pub camera_effects: bool,
+    pub fullscreen: bool,
+    pub vsync: bool,
+    pub show_fps: bool,
+    pub difficulty: Difficulty,
+    pub adaptive_difficulty: bool,
+    pub key_bindings: KeyBindings,
+}
+
+impl Default for GameSettings{
+    fn default() -> Self {
+        Self {
+            master_volume: 1.,
+            music_volume: 0.6,
+            sfx_volume: 0.8,
+            camera_effects: true,
+            fullscreen: false,
+            vsync: true,
+            show_fps: true,
+            difficulty: Difficulty::Normal,
+            adaptive_difficulty: false,
+            key_bindings: KeyBindings::default(),
+        }
+    }
+}
+
+impl GameSettings{
+    pub fn load(path: &str) -> Self{
+        match fs::read_to_string(path){
+            Ok(contents) => Self::parse(&contents),
+            Err(_) => Self::default(),
+        }
+    }
+
+    pub fn save(&self, path: &str) -> io::Result<()>{
+        fs::write(path, self.serialize())
+    }
+
+    pub fn parse(contents: &str) -> Self{
+        let mut settings = Self::default();
+
+        for (key, value) in key_values(contents){
+            settings.set(key, value);
+        }
+
+        settings
+    }
+
+    pub fn serialize(&self) -> String{
+        let mut contents = format!(
+            "master_volume = {}\nmusic_volume = {}\nsfx_volume = {}\ncamera_effects = {}\nfullscreen = {}\nvsync = {}\nshow_fps = {}\ndifficulty = {}\nadaptive_difficulty = {}\n",
+            self.master_volume, self.music_volume, self.sfx_volume, self.camera_effects,
+            self.fullscreen, self.vsync, self.show_fps, self.difficulty.name(), self.adaptive_difficulty
+        );
+
+        for action in KeyAction::ALL{
+            contents += &format!("{} = {}\n", action.key(), key_name(self.key_bindings.get(action)));
+        }
+
+        contents
+    }
+
+    fn set(&mut self, key: &str, value: &str){
+        let volume = || value.parse::<f32>().ok().map(|volume| volume.clamp(0., 1.));
+
+        match key{
+            "master_volume" => if let Some(volume) = volume() { self.master_volume = volume },
+            "music_volume" => if let Some(volume) = volume() { self.music_volume = volume },
+            "sfx_volume" => if let Some(volume) = volume() { self.sfx_volume = volume },
+            "camera_effects" => if let Ok(enabled) = value.parse() { self.camera_effects = enabled },
+            "fullscreen" => if let Ok(enabled) = value.parse() { self.fullscreen = enabled },
+            "vsync" => if let Ok(enabled) = value.parse() { self.vsync = enabled },
+            "show_fps" => if let Ok(enabled) = value.parse() { self.show_fps = enabled },
+            "difficulty" => if let Some(difficulty) = Difficulty::parse(value) { self.difficulty = difficulty },
+            "adaptive_difficulty" => if let Ok(enabled) = value.parse() { self.adaptive_difficulty = enabled },
+            _ => {
+                let action = KeyAction::ALL.iter().find(|action| action.key() == key);
+                if let (Some(action), Some(bound)) = (action, parse_key(value)){
+                    self.key_bindings.set(*action, bound);
+                }
+            }
+        }
+    }
+
+    pub fn effective_music_volume(&self) -> f32{
+        self.master_volume * self.music_volume
+    }
+
+    pub fn effective_sfx_volume(&self) -> f32{
+        self.master_volume * self.sfx_volume
+    }
+}
+
+//also runs on the first frame, which is how the saved settings are applied on startup
+fn apply_video_settings(settings: Res<GameSettings>, mut windows: ResMut<Windows>, mut fps_query: Query<&mut Style, With<FpsText>>){
+    if !settings.is_changed(){
+        return;
+    }
+
+    if let Some(window) = windows.get_primary_mut(){
+        let mode = if settings.fullscreen { WindowMode::BorderlessFullscreen } else { WindowMode::Windowed };
+        if window.mode() != mode{
+            window.set_mode(mode);
+        }
+
+        let present_mode = if settings.vsync { PresentMode::Fifo } else { PresentMode::Immediate };
+        if window.present_mode() != present_mode{
+            window.set_present_mode(present_mode);
+        }
+    }
+
+    for mut style in fps_query.iter_mut(){
+        style.display = if settings.show_fps { Display::Flex } else { Display::None };
+    }
+}
+
+fn save_settings(settings: Res<GameSettings>){
+    if settings.is_changed() && !settings.is_added(){
+        if let Err(error) = settings.save(SETTINGS_FILE){
+            warn!("failed to save settings to {}: {}", SETTINGS_FILE, error);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn settings_round_trip(){
+        let mut settings = GameSettings{
+            master_volume: 0.5,
+            camera_effects: false,
+            fullscreen: true,
+            difficulty: Difficulty::Hard,
+            adaptive_difficulty: true,
+            ..default()
+        };
+        settings.key_bindings.set(KeyAction::Fire, KeyCode::Z);
+
+        assert_eq!(GameSettings::parse(&settings.serialize()), settings);
+    }
+
+    #[test]
+    fn unknown_keys_and_bad_values_keep_the_defaults(){
+        let settings = GameSettings::parse("colour_blind = true\nvsync = maybe\ndifficulty = nightmare\nfire = NotAKey\n");
+        assert_eq!(settings, GameSettings::default());
+    }
+
+    #[test]
+    fn volumes_are_clamped(){
+        let settings = GameSettings::parse("master_volume = 3\nmusic_volume = -1\n");
+        assert_eq!(settings.master_volume, 1.);
+        assert_eq!(settings.music_volume, 0.);
+    }
+
+    #[test]
+    fn difficulty_names_ignore_case(){
+        assert_eq!(GameSettings::parse("difficulty = EASY").difficulty, Difficulty::Easy);
+    }
+}
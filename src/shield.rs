@@ -0,0 +1,109 @@
+use bevy::prelude::*;
+use crate::components::{GameState, Shield, ShieldBubble};
+use crate::utils::ScaledTime;
+
+const SHIELD_RECHARGE_PER_SECOND: f32 = 0.5;
+const SHIELD_BUBBLE_SIZE: f32 = 80.;
+const SHIELD_BUBBLE_COLOR: Color = Color::rgba(0.4, 0.8, 1.0, 0.8);
+
+pub struct ShieldPlugin;
+
+impl Plugin for ShieldPlugin{
+    fn build(&self, app: &mut App) {
+        app
+        .add_system_set(
+            SystemSet::on_update(GameState::Playing)
+            .with_system(recharge_shield)
+            .with_system(update_shield_bubble)
+        );
+    }
+}
+
+//spawned as a child of the player, drawn just above it
+pub fn shield_bubble(texture: Handle<Image>) -> SpriteBundle{
+    SpriteBundle{
+        texture,
+        sprite: Sprite{
+            color: SHIELD_BUBBLE_COLOR,
+            custom_size: Some(Vec2::splat(SHIELD_BUBBLE_SIZE)),
+            ..default()
+        },
+        transform: Transform::from_xyz(0., 0., 0.5),
+        ..default()
+    }
+}
+
+fn recharge_shield(time: ScaledTime, mut query: Query<&mut Shield>){
+    let dt = time.delta_seconds();
+
+    for mut shield in query.iter_mut(){
+        shield.recharge(SHIELD_RECHARGE_PER_SECOND * dt, dt);
+    }
+}
+
+//the bubble fades out as the shield drains
+fn update_shield_bubble(shield_query: Query<(&Shield, &Children)>, mut bubble_query: Query<&mut Sprite, With<ShieldBubble>>){
+    for (shield, children) in shield_query.iter(){
+        let charge = if shield.capacity > 0. { shield.current / shield.capacity } else { 0. };
+
+        for child in children.iter(){
+            if let Ok(mut sprite) = bubble_query.get_mut(*child){
+                let alpha = SHIELD_BUBBLE_COLOR.a() * charge;
+                if sprite.color.a() != alpha{
+                    sprite.color.set_a(alpha);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::components::Health;
+    use crate::{PLAYER_MAX_HEALTH, PLAYER_SHIELD_CAPACITY, SHIELD_RECHARGE_DELAY};
+
+    #[test]
+    fn shield_absorbs_before_health(){
+        let mut shield = Shield::new(2.);
+        let mut health = Health(PLAYER_MAX_HEALTH);
+
+        assert!(!health.take_damage(1.5, Some(&mut shield)));
+        assert_eq!(shield.current, 0.5);
+        assert_eq!(health.0, PLAYER_MAX_HEALTH);
+
+        assert!(!health.take_damage(1., Some(&mut shield)));
+        assert_eq!(shield.current, 0.);
+        assert_eq!(health.0, PLAYER_MAX_HEALTH - 0.5);
+    }
+
+    //a partly recharged shield leaves a fraction of the hit, health steps past zero without equalling it
+    #[test]
+    fn fractional_leftover_damage_still_kills(){
+        let mut shield = Shield::new(PLAYER_SHIELD_CAPACITY);
+        shield.absorb(PLAYER_SHIELD_CAPACITY);
+        shield.recharge_delay = 0.;
+        shield.recharge(SHIELD_RECHARGE_PER_SECOND * 0.35, 0.35);
+
+        let mut health = Health(1.);
+        assert!(!health.take_damage(1., Some(&mut shield)));
+        assert!(health.0 > 0. && health.0 < 1.);
+
+        assert!(health.take_damage(1., Some(&mut shield)));
+        assert!(health.0 < 0.);
+    }
+
+    #[test]
+    fn recharge_waits_for_the_delay(){
+        let mut shield = Shield::new(2.);
+        shield.absorb(2.);
+
+        shield.recharge(1., SHIELD_RECHARGE_DELAY / 2.);
+        assert_eq!(shield.current, 0.);
+        shield.recharge(1., SHIELD_RECHARGE_DELAY);
+        shield.recharge(1., 1.);
+        assert_eq!(shield.current, 1.);
+        shield.recharge(5., 1.);
+        assert_eq!(shield.current, shield.capacity);
+    }
+}
@@ -0,0 +1,370 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::io;
+use bevy::prelude::*;
+use bevy::utils::{HashMap, HashSet};
+use bevy::ecs::schedule::ShouldRun;
+use bevy::window::WindowCloseRequested;
+use crate::components::{AchievementText, Autopilot, BulletCancelled, BulletFired, EnemyHit, EnemyKilled, GameState, PlayerDied, WaveCleared};
+use crate::utils::{config_lines, key_values, run_if_playing};
+
+pub const STATS_FILE: &str = "stats.cfg";
+const ACHIEVEMENTS_FILE: &str = "assets/data/achievements.cfg";
+const NOTIFICATION_SECONDS: f32 = 3.;
+
+pub struct StatsPlugin;
+
+impl Plugin for StatsPlugin{
+    fn build(&self, app: &mut App) {
+        app
+        .insert_resource(Stats::load(STATS_FILE))
+        .insert_resource(Achievements::load(ACHIEVEMENTS_FILE))
+        .insert_resource(AchievementNotifications::default())
+        .add_system_set(
+            SystemSet::new()
+            .with_run_criteria(player_in_control.chain(run_if_playing))
+            .with_system(track_run_stats)
+        )
+        .add_system_set(
+            SystemSet::new()
+            .with_run_criteria(player_in_control)
+            .with_system(count_event_stats)
+            .with_system(save_stats_on_menu)
+            .with_system(save_stats_on_close)
+        )
+        .add_system(unlock_achievements)
+        .add_system(show_achievement_notifications);
+    }
+}
+
+//Games played by the autopilot, demos and simulations, don't count towards the stats.
+//Its events can still be read for two frames after it hands back control, so counting waits until they are gone
+fn player_in_control(autopilot: Res<Autopilot>, mut frames_since_autopilot: Local<u8>) -> ShouldRun{
+    if autopilot.enabled{
+        *frames_since_autopilot = 0;
+        return ShouldRun::No;
+    }
+
+    if *frames_since_autopilot < 2{
+        *frames_since_autopilot += 1;
+        ShouldRun::No
+    }else{
+        ShouldRun::Yes
+    }
+}
+
+//Lifetime totals across every run, persisted as `key = value` lines
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Stats{
+    pub shots_fired: u32,
+    pub hits: u32,
+    pub kills: HashMap<String, u32>,
+    pub bullets_cancelled: u32,
+    pub deaths: u32,
+    pub time_played: f32,
+    pub best_wave: u32,
+    pub unlocked: HashSet<String>,
+}
+
+impl Stats{
+    pub fn load(path: &str) -> Self{
+        match fs::read_to_string(path){
+            Ok(contents) => Self::parse(&contents),
+            Err(_) => Self::default(),
+        }
+    }
+
+    pub fn save(&self, path: &str) -> io::Result<()>{
+        fs::write(path, self.serialize())
+    }
+
+    pub fn parse(contents: &str) -> Self{
+        let mut stats = Self::default();
+
+        for (key, value) in key_values(contents){
+            stats.set(key, value);
+        }
+
+        stats
+    }
+
+    pub fn serialize(&self) -> String{
+        let mut contents = format!(
+            "shots_fired = {}\nhits = {}\nbullets_cancelled = {}\ndeaths = {}\ntime_played = {}\nbest_wave = {}\n",
+            self.shots_fired, self.hits, self.bullets_cancelled, self.deaths, self.time_played, self.best_wave
+        );
+
+        let mut kills: Vec<_> = self.kills.iter().collect();
+        kills.sort();
+        for (archetype, count) in kills{
+            contents += &format!("kills_{} = {}\n", archetype, count);
+        }
+
+        let mut unlocked: Vec<_> = self.unlocked.iter().map(String::as_str).collect();
+        unlocked.sort_unstable();
+        contents += &format!("unlocked = {}\n", unlocked.join(","));
+
+        contents
+    }
+
+    fn set(&mut self, key: &str, value: &str){
+        match key{
+            "shots_fired" => if let Ok(count) = value.parse() { self.shots_fired = count },
+            "hits" => if let Ok(count) = value.parse() { self.hits = count },
+            "bullets_cancelled" => if let Ok(count) = value.parse() { self.bullets_cancelled = count },
+            "deaths" => if let Ok(count) = value.parse() { self.deaths = count },
+            "time_played" => if let Ok(seconds) = value.parse() { self.time_played = seconds },
+            "best_wave" => if let Ok(wave) = value.parse() { self.best_wave = wave },
+            "unlocked" => self.unlocked = value.split(',').map(str::trim).filter(|id| !id.is_empty()).map(String::from).collect(),
+            _ => {
+                if let (Some(archetype), Ok(count)) = (key.strip_prefix("kills_"), value.parse()){
+                    self.kills.insert(archetype.to_string(), count);
+                }
+            }
+        }
+    }
+
+    pub fn shot_fired(&mut self){
+        self.shots_fired += 1;
+    }
+
+    pub fn hit(&mut self){
+        self.hits += 1;
+    }
+
+    pub fn bullet_cancelled(&mut self){
+        self.bullets_cancelled += 1;
+    }
+
+    pub fn enemy_killed(&mut self, archetype: Option<&str>){
+        let name = archetype.unwrap_or("unknown");
+        *self.kills.entry(name.to_string()).or_insert(0) += 1;
+    }
+
+    pub fn total_kills(&self) -> u32{
+        self.kills.values().sum()
+    }
+
+    //hits per shot in percent
+    pub fn accuracy(&self) -> f32{
+        if self.shots_fired == 0 { 0. } else { self.hits as f32 / self.shots_fired as f32 * 100. }
+    }
+
+    //stat names as used in the achievements file
+    pub fn value(&self, stat: &str) -> Option<f32>{
+        let value = match stat{
+            "shots_fired" => self.shots_fired as f32,
+            "hits" => self.hits as f32,
+            "accuracy" => self.accuracy(),
+            "kills" => self.total_kills() as f32,
+            "bullets_cancelled" => self.bullets_cancelled as f32,
+            "deaths" => self.deaths as f32,
+            "time_played" => self.time_played,
+            "best_wave" => self.best_wave as f32,
+            _ => *self.kills.get(stat.strip_prefix("kills_")?).unwrap_or(&0) as f32,
+        };
+        Some(value)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Achievement{
+    pub id: String,
+    pub title: String,
+    pub stat: String,
+    pub threshold: f32,
+}
+
+//Definitions read from the achievements data file
+#[derive(Debug, Clone, Default)]
+pub struct Achievements(pub Vec<Achievement>);
+
+impl Achievements{
+    pub fn load(path: &str) -> Self{
+        match fs::read_to_string(path){
+            Ok(contents) => Self::parse(&contents),
+            Err(error) => {
+                warn!("failed to read achievements from {}: {}", path, error);
+                Self::default()
+            }
+        }
+    }
+
+    //`id = title | stat | threshold`, malformed lines are skipped with a warning
+    pub fn parse(contents: &str) -> Self{
+        let mut achievements = Vec::new();
+
+        for line in config_lines(contents){
+            let parsed = line.split_once('=').and_then(|(id, definition)| {
+                let parts: Vec<&str> = definition.split('|').map(str::trim).collect();
+                match parts[..]{
+                    [title, stat, threshold] => Some(Achievement{
+                        id: id.trim().to_string(),
+                        title: title.to_string(),
+                        stat: stat.to_string(),
+                        threshold: threshold.parse().ok()?,
+                    }),
+                    _ => None,
+                }
+            });
+
+            match parsed{
+                Some(achievement) => achievements.push(achievement),
+                None => warn!("skipping malformed achievement: {}", line),
+            }
+        }
+
+        Self(achievements)
+    }
+}
+
+#[derive(Default)]
+pub struct AchievementNotifications{
+    pub queue: VecDeque<String>,
+    pub remaining: f32,
+}
+
+fn track_run_stats(time: Res<Time>, mut stats: ResMut<Stats>){
+    stats.time_played += time.delta_seconds();
+}
+
+//deaths in the same frame count once, like they do for the player's lives
+fn count_event_stats(mut stats: ResMut<Stats>, mut bullets_fired: EventReader<BulletFired>, mut hits: EventReader<EnemyHit>, mut cancelled: EventReader<BulletCancelled>,
+    mut kills: EventReader<EnemyKilled>, mut deaths: EventReader<PlayerDied>, mut waves_cleared: EventReader<WaveCleared>){
+
+    for _ in bullets_fired.iter().filter(|bullet| bullet.from_player){
+        stats.shot_fired();
+    }
+    for _ in hits.iter(){
+        stats.hit();
+    }
+    for _ in cancelled.iter(){
+        stats.bullet_cancelled();
+    }
+    for kill in kills.iter(){
+        stats.enemy_killed(kill.archetype);
+    }
+    if deaths.iter().count() > 0{
+        stats.deaths += 1;
+    }
+    if let Some(wave) = waves_cleared.iter().map(|wave| wave.number + 1).max(){
+        stats.best_wave = stats.best_wave.max(wave);
+    }
+}
+
+fn unlock_achievements(mut stats: ResMut<Stats>, achievements: Res<Achievements>, mut notifications: ResMut<AchievementNotifications>){
+    if !stats.is_changed(){
+        return;
+    }
+
+    let mut unlocked_any = false;
+    for achievement in achievements.0.iter(){
+        if stats.unlocked.contains(&achievement.id){
+            continue;
+        }
+
+        if stats.value(&achievement.stat).is_some_and(|value| value >= achievement.threshold){
+            stats.unlocked.insert(achievement.id.clone());
+            notifications.queue.push_back(achievement.title.clone());
+            info!("achievement unlocked: {}", achievement.title);
+            unlocked_any = true;
+        }
+    }
+
+    if unlocked_any{
+        if let Err(error) = stats.save(STATS_FILE){
+            warn!("failed to save stats to {}: {}", STATS_FILE, error);
+        }
+    }
+}
+
+//one notification at a time, each shown for a few seconds
+fn show_achievement_notifications(time: Res<Time>, mut notifications: ResMut<AchievementNotifications>, mut query: Query<&mut Text, With<AchievementText>>){
+    notifications.remaining -= time.delta_seconds();
+
+    if notifications.remaining <= 0.{
+        let message = match notifications.queue.pop_front(){
+            Some(title) => {
+                notifications.remaining = NOTIFICATION_SECONDS;
+                format!("Achievement unlocked: {}", title)
+            },
+            None => String::new(),
+        };
+
+        for mut text in query.iter_mut(){
+            if text.sections[1].value != message{
+                text.sections[1].value = message.clone();
+            }
+        }
+    }
+}
+
+fn save_stats(stats: Res<Stats>){
+    if let Err(error) = stats.save(STATS_FILE){
+        warn!("failed to save stats to {}: {}", STATS_FILE, error);
+    }
+}
+
+//back on the main menu after a run
+fn save_stats_on_menu(state: Res<State<GameState>>, stats: Res<Stats>){
+    if state.is_changed() && *state.current() == GameState::MainMenu{
+        save_stats(stats);
+    }
+}
+
+fn save_stats_on_close(mut close_events: EventReader<WindowCloseRequested>, stats: Res<Stats>){
+    if close_events.iter().count() > 0{
+        save_stats(stats);
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn stats_round_trip(){
+        let mut stats = Stats { shots_fired: 40, hits: 10, bullets_cancelled: 3, deaths: 2, time_played: 95.5, best_wave: 4, ..default() };
+        stats.enemy_killed(Some("turret"));
+        stats.enemy_killed(None);
+        stats.unlocked.insert("first_blood".to_string());
+
+        assert_eq!(Stats::parse(&stats.serialize()), stats);
+    }
+
+    #[test]
+    fn stat_values_by_name(){
+        let mut stats = Stats { shots_fired: 4, hits: 1, ..default() };
+        stats.enemy_killed(Some("kamikaze"));
+
+        assert_eq!(stats.value("accuracy"), Some(25.));
+        assert_eq!(stats.value("kills"), Some(1.));
+        assert_eq!(stats.value("kills_kamikaze"), Some(1.));
+        assert_eq!(stats.value("kills_turret"), Some(0.));
+        assert_eq!(stats.value("luck"), None);
+    }
+
+    #[test]
+    fn achievements_parse(){
+        let achievements = Achievements::parse("# comment\nfirst_blood = First blood | kills | 1\n\nveteran = Veteran | best_wave | 10\n");
+        assert_eq!(achievements.0, vec![
+            Achievement { id: "first_blood".to_string(), title: "First blood".to_string(), stat: "kills".to_string(), threshold: 1. },
+            Achievement { id: "veteran".to_string(), title: "Veteran".to_string(), stat: "best_wave".to_string(), threshold: 10. },
+        ]);
+    }
+
+    #[test]
+    fn malformed_achievements_are_skipped(){
+        let achievements = Achievements::parse("no_equals\nmissing = Missing | kills\nbad = Bad | kills | lots\nextra = Extra | kills | 1 | 2\nok = Ok | deaths | 3\n");
+        assert_eq!(achievements.0.iter().map(|achievement| achievement.id.as_str()).collect::<Vec<_>>(), vec!["ok"]);
+    }
+
+    #[test]
+    fn shipped_achievements_use_known_stats(){
+        let achievements = Achievements::parse(include_str!("../assets/data/achievements.cfg"));
+        assert!(!achievements.0.is_empty());
+        for achievement in achievements.0.iter(){
+            assert!(Stats::default().value(&achievement.stat).is_some(), "{} uses an unknown stat", achievement.id);
+        }
+    }
+}
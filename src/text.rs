@@ -1,5 +1,6 @@
 use bevy::{prelude::*, diagnostic::{FrameTimeDiagnosticsPlugin, Diagnostics}};
-use crate::*;
+use crate::components::{AchievementText, BombText, FpsText, Health, HealthIcon, HudRoot, LivesText, Player, PlayerState, Score, ScoreText, Wave, WaveText};
+use crate::PLAYER_MAX_HEALTH;
 
 
 pub struct TextPlugin;
@@ -7,135 +8,182 @@ pub struct TextPlugin;
 impl Plugin for TextPlugin{
     fn build(&self, app: &mut App) {
         app
-        .add_plugin(FrameTimeDiagnosticsPlugin::default())
-        .add_startup_system(spawn_fps_text)
+        .add_plugin(FrameTimeDiagnosticsPlugin)
+        .add_startup_system(spawn_hud)
         .add_system(update_fps_text)
         .add_system(update_score_text)
-        .add_system(update_health_text);
+        .add_system(update_wave_text)
+        .add_system(update_lives_text)
+        .add_system(update_bomb_text)
+        .add_system(update_health_icons);
 
     }
 }
 
+const HUD_FONT: &str = "fonts/FiraSans-Regular.ttf";
+const HUD_FONT_SIZE: f32 = 20.;
+const HUD_MARGIN: f32 = 10.;
+const HEALTH_ICON: &str = "images/player/player_health_icon.png";
+const HEALTH_ICON_SIZE: f32 = 24.;
+const HEALTH_ICON_LOST_COLOR: Color = Color::rgba(1.0, 1.0, 1.0, 0.2);
+const SCORE_DIGITS: usize = 6;
+
+#[derive(Clone, Copy)]
+enum HudAnchor{
+    TopLeft,
+    TopCenter,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+#[derive(Clone, Copy)]
+enum HudElement{
+    Fps,
+    Score,
+    Wave,
+    Lives,
+    Bombs,
+    HealthIcons,
+    Achievement,
+}
 
-const HEALTH_ICON_POSITION: f32 = (-WINDOW_HEIGHT/2.) + 100.;
+//The HUD as data: each corner of the arena stacks its elements from the edge inwards
+const HUD_LAYOUT: &[(HudAnchor, &[HudElement])] = &[
+    (HudAnchor::TopLeft, &[HudElement::Score, HudElement::Wave]),
+    (HudAnchor::TopCenter, &[HudElement::Achievement]),
+    (HudAnchor::TopRight, &[HudElement::Fps]),
+    (HudAnchor::BottomLeft, &[HudElement::HealthIcons]),
+    (HudAnchor::BottomRight, &[HudElement::Bombs, HudElement::Lives]),
+];
 
-fn spawn_fps_text(mut commands: Commands, asset_server: Res<AssetServer>){
+fn spawn_hud(mut commands: Commands, asset_server: Res<AssetServer>){
+    let font = asset_server.load(HUD_FONT);
+    let health_icon = asset_server.load(HEALTH_ICON);
+
+    //the root is resized to the letterboxed arena by the arena plugin
     commands
-    .spawn_bundle(TextBundle {
+    .spawn_bundle(NodeBundle {
         style: Style {
-            align_self: AlignSelf::FlexEnd,
             position_type: PositionType::Absolute,
-            position: Rect {
-                bottom: Val::Px(5.0),
-                right: Val::Px(15.0),
-                ..default()
-            },
-            ..default()
-        },
-
-        text:Text{
-            sections: vec![
-                TextSection{
-                    value: "Score: ".to_string(),
-                    style: TextStyle {
-                        font_size: 20.0,
-                        color: Color::WHITE,
-                        font: asset_server.load("fonts/FiraSans-Regular.ttf"),
-                    },
-                },
-                TextSection {
-                    value: "".to_string(),
-                    style: TextStyle {
-                        font_size: 20.0,
-                        color: Color::GOLD,
-                        font: asset_server.load("fonts/FiraSans-Regular.ttf"),
-                    },
-                },
-            ],
             ..default()
         },
+        color: UiColor(Color::NONE),
         ..default()
     })
-    .insert(ScoreText);
-
-    commands.spawn_bundle(TextBundle{
-        style: Style{
-            align_self: AlignSelf::FlexEnd,
-            ..default()
-        },
+    .insert(HudRoot)
+    .with_children(|parent| {
+        for (anchor, elements) in HUD_LAYOUT{
+            parent
+            .spawn_bundle(NodeBundle {
+                style: anchor_style(*anchor),
+                color: UiColor(Color::NONE),
+                ..default()
+            })
+            .with_children(|parent| {
+                for element in elements.iter(){
+                    spawn_element(parent, *element, &font, &health_icon);
+                }
+            });
+        }
+    });
+}
 
-        text: Text{
-            sections: vec![
-                TextSection{
-                    value: "FPS: ".to_string(),
-                    style: TextStyle {
-                        font_size: 20.0,
-                        color: Color::WHITE,
-                        font: asset_server.load("fonts/FiraSans-Regular.ttf"),
-                    },
-                },
-                TextSection {
-                    value: "".to_string(),
-                    style: TextStyle {
-                        font_size: 20.0,
-                        color: Color::GOLD,
-                        font: asset_server.load("fonts/FiraSans-Regular.ttf"),
-                    },
-                },
-            ],
-            ..default()
-        },
+fn anchor_style(anchor: HudAnchor) -> Style{
+    let margin = Val::Px(HUD_MARGIN);
+    let (position, align_items) = match anchor{
+        HudAnchor::TopLeft => (Rect { top: margin, left: margin, ..default() }, AlignItems::FlexStart),
+        HudAnchor::TopCenter => (Rect { top: margin, left: Val::Px(0.), right: Val::Px(0.), ..default() }, AlignItems::Center),
+        HudAnchor::TopRight => (Rect { top: margin, right: margin, ..default() }, AlignItems::FlexEnd),
+        HudAnchor::BottomLeft => (Rect { bottom: margin, left: margin, ..default() }, AlignItems::FlexStart),
+        HudAnchor::BottomRight => (Rect { bottom: margin, right: margin, ..default() }, AlignItems::FlexEnd),
+    };
+
+    Style {
+        position_type: PositionType::Absolute,
+        position,
+        //ui y grows upwards, reverse the column so the first element is at the top
+        flex_direction: FlexDirection::ColumnReverse,
+        align_items,
         ..default()
-    })
-    .insert(FpsText);
+    }
+}
 
-    commands.spawn_bundle(SpriteBundle {
-        texture: asset_server.load("images/player/player_health_icon.png"),
-        transform: Transform{
-            translation: Vec3::new(-35., HEALTH_ICON_POSITION - 75. , 5.),
-            scale: Vec3::new(0.5, 0.5, 0.5),
-            ..default()
+fn spawn_element(parent: &mut ChildBuilder, element: HudElement, font: &Handle<Font>, health_icon: &Handle<Image>){
+    match element{
+        HudElement::Fps => {
+            parent.spawn_bundle(label("FPS: ", font)).insert(FpsText);
         },
-        ..default()
-    });
-
-    commands.spawn_bundle(TextBundle {
-        style: Style {
-            align_self: AlignSelf::FlexEnd,
-            position_type: PositionType::Absolute,
-            position: Rect {
-                bottom: Val::Px(15.0),
-                right: Val::Px(250.0),
+        HudElement::Score => {
+            parent.spawn_bundle(label("Score: ", font)).insert(ScoreText);
+        },
+        HudElement::Wave => {
+            parent.spawn_bundle(label("Wave: ", font)).insert(WaveText);
+        },
+        HudElement::Lives => {
+            parent.spawn_bundle(label("Lives: ", font)).insert(LivesText);
+        },
+        HudElement::Bombs => {
+            parent.spawn_bundle(label("Bombs: ", font)).insert(BombText);
+        },
+        HudElement::Achievement => {
+            parent.spawn_bundle(label("", font)).insert(AchievementText);
+        },
+        HudElement::HealthIcons => {
+            parent
+            .spawn_bundle(NodeBundle {
+                style: Style {
+                    flex_direction: FlexDirection::Row,
+                    ..default()
+                },
+                color: UiColor(Color::NONE),
                 ..default()
-            },
-            ..default()
+            })
+            .with_children(|parent| {
+                for index in 0..PLAYER_MAX_HEALTH as u32{
+                    parent
+                    .spawn_bundle(ImageBundle {
+                        style: Style {
+                            size: Size::new(Val::Px(HEALTH_ICON_SIZE), Val::Px(HEALTH_ICON_SIZE)),
+                            margin: Rect { right: Val::Px(4.), ..default() },
+                            ..default()
+                        },
+                        image: UiImage(health_icon.clone()),
+                        ..default()
+                    })
+                    .insert(HealthIcon(index));
+                }
+            });
         },
+    }
+}
 
-        text:Text{
+//white caption followed by a gold value section that the update systems fill in
+fn label(caption: &str, font: &Handle<Font>) -> TextBundle{
+    TextBundle {
+        text: Text {
             sections: vec![
-                TextSection{
-                    value: "HEALTH: ".to_string(),
+                TextSection {
+                    value: caption.to_string(),
                     style: TextStyle {
-                        font_size: 20.0,
+                        font_size: HUD_FONT_SIZE,
                         color: Color::WHITE,
-                        font: asset_server.load("fonts/FiraSans-Regular.ttf"),
+                        font: font.clone(),
                     },
                 },
                 TextSection {
                     value: "".to_string(),
                     style: TextStyle {
-                        font_size: 20.0,
+                        font_size: HUD_FONT_SIZE,
                         color: Color::GOLD,
-                        font: asset_server.load("fonts/FiraSans-Regular.ttf"),
+                        font: font.clone(),
                     },
                 },
             ],
             ..default()
         },
         ..default()
-    })
-    .insert(HealthText);
-
+    }
 }
 
 fn update_fps_text(diagnostics: Res<Diagnostics>, mut query: Query<&mut Text, With<FpsText>>){
@@ -150,23 +198,37 @@ fn update_fps_text(diagnostics: Res<Diagnostics>, mut query: Query<&mut Text, Wi
 
 fn update_score_text(score: Res<Score>, mut query: Query<&mut Text, With<ScoreText>>){
     for mut text in query.iter_mut(){
-        text.sections[1].value = format!("{:.2}", score.0);
+        text.sections[1].value = format!("{:0width$}", score.0, width = SCORE_DIGITS);
     }
 }
 
-fn update_health_text( player_query: Query<&Health, With<Player>>,mut text_query: Query<&mut Text, With<HealthText>>){
+fn update_wave_text(wave: Res<Wave>, mut query: Query<&mut Text, With<WaveText>>){
+    for mut text in query.iter_mut(){
+        text.sections[1].value = wave.number.to_string();
+    }
+}
 
-    for health in player_query.iter(){
-        for mut text in text_query.iter_mut(){
+fn update_lives_text(player_state: Res<PlayerState>, mut query: Query<&mut Text, With<LivesText>>){
+    for mut text in query.iter_mut(){
+        text.sections[1].value = player_state.lives.to_string();
+    }
+}
 
-            if health.0 == 0.{
-                text.sections[1].value = format!("{:.2}", 0);
-            }else{
-                text.sections[1].value = format!("{:.2}", health.0 as u32);
-            }
-        }
+fn update_bomb_text(player_state: Res<PlayerState>, mut query: Query<&mut Text, With<BombText>>){
+    for mut text in query.iter_mut(){
+        text.sections[1].value = player_state.bombs.to_string();
     }
+}
+
+//lost hit points stay in the row as faded icons
+fn update_health_icons(player_query: Query<&Health, With<Player>>, mut icon_query: Query<(&HealthIcon, &mut UiColor)>){
 
-    
+    let health = player_query.get_single().map_or(0., |health| health.0.max(0.));
 
+    for (icon, mut color) in icon_query.iter_mut(){
+        let target = if (icon.0 as f32) < health { Color::WHITE } else { HEALTH_ICON_LOST_COLOR };
+        if color.0 != target{
+            color.0 = target;
+        }
+    }
 }
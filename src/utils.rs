@@ -1,7 +1,137 @@
 
-use crate::*;
+use std::marker::PhantomData;
+use bevy::prelude::*;
+use bevy::diagnostic::{Diagnostic, DiagnosticId, Diagnostics};
+use bevy::ecs::system::SystemParam;
+use bevy::ecs::schedule::ShouldRun;
+use bevy::utils::Duration;
+use rand::thread_rng;
+use crate::audio::{SoundEffect, SoundQueue};
+use crate::components::{AnimationTimer, Bullet, BulletPool, CameraEffects, EffectKind, Enemy, EnemyCount, Explosion, ExplosionPool, ExplosionTimer, ExplosionToSpawn, GameClock, GameEffects, GameState, MovableObject, TimeScale, Velocity};
+use crate::{ARENA_HEIGHT, ARENA_WIDTH, GAME_SPEED, TIME_STEP};
+use crate::particle::spawn_particles;
 
-pub fn animate_object(time: Res<Time>, texture_atlases: Res<Assets<TextureAtlas>>,mut query: Query<(&mut AnimationTimer, &mut TextureAtlasSprite, &Handle<TextureAtlas>,)>){
+const ARENA_DESPAWN_MARGIN: f32 = 100.;
+
+pub const BULLET_POOL_SIZE: DiagnosticId = DiagnosticId::from_u128(112853079356316545716541489452227960001);
+pub const BULLET_POOL_HIGH_WATER_MARK: DiagnosticId = DiagnosticId::from_u128(112853079356316545716541489452227960002);
+pub const EXPLOSION_POOL_SIZE: DiagnosticId = DiagnosticId::from_u128(112853079356316545716541489452227960003);
+pub const EXPLOSION_POOL_HIGH_WATER_MARK: DiagnosticId = DiagnosticId::from_u128(112853079356316545716541489452227960004);
+
+#[derive(SystemLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum GameSystem{
+    Input,
+    Movement,
+    Beams,
+}
+
+//game clock time with the hit-stop time scale applied
+#[derive(SystemParam)]
+pub struct ScaledTime<'w, 's>{
+    clock: Res<'w, GameClock>,
+    time_scale: Res<'w, TimeScale>,
+    #[system_param(ignore)]
+    marker: PhantomData<&'s ()>,
+}
+
+impl<'w, 's> ScaledTime<'w, 's>{
+    pub fn delta_seconds(&self) -> f32{
+        self.clock.delta_seconds() * self.time_scale.0
+    }
+
+    pub fn delta(&self) -> Duration{
+        self.clock.delta().mul_f32(self.time_scale.0)
+    }
+}
+
+//lines of the `key = value` files (settings, save, stats and data files), blank lines and # comments are skipped
+pub fn config_lines(contents: &str) -> impl Iterator<Item = &str>{
+    contents.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#'))
+}
+
+//lines without a `=` are skipped, keys and values are trimmed
+pub fn key_values(contents: &str) -> impl Iterator<Item = (&str, &str)>{
+    config_lines(contents)
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, value)| (key.trim(), value.trim()))
+}
+
+//stands still on the loading screen, in menus and while paused
+pub fn advance_game_clock(time: Res<Time>, state: Res<State<GameState>>, mut clock: ResMut<GameClock>){
+    if *state.current() == GameState::Playing{
+        clock.advance(time.delta_seconds());
+    }else{
+        clock.pause();
+    }
+}
+
+//runs once every given number of game clock seconds, FixedTimestep would follow the frame time instead
+pub fn every(seconds: f32) -> impl FnMut(Res<GameClock>, Local<f32>) -> ShouldRun{
+    move |clock: Res<GameClock>, mut elapsed: Local<f32>|{
+        *elapsed += clock.delta_seconds();
+        if *elapsed >= seconds{
+            *elapsed -= seconds;
+            ShouldRun::Yes
+        }else{
+            ShouldRun::No
+        }
+    }
+}
+
+//for chaining after run criteria such as every(), which cannot be combined with State run criteria
+pub fn run_if_playing(In(should_run): In<ShouldRun>, state: Res<State<GameState>>) -> ShouldRun{
+    if *state.current() == GameState::Playing{
+        should_run
+    }else{
+        ShouldRun::No
+    }
+}
+
+pub fn movable_system(time_scale: Res<TimeScale>, mut query: Query<(&Velocity, &mut Transform), With<MovableObject>>){
+
+    for (velocity, mut transform) in query.iter_mut(){
+        let translation = &mut transform.translation;
+        translation.x += velocity.x * TIME_STEP * GAME_SPEED * time_scale.0;
+        translation.y += velocity.y * TIME_STEP * GAME_SPEED * time_scale.0;
+    }
+}
+
+type Culled = (Entity, &'static MovableObject, &'static Velocity, &'static Transform, Option<&'static Enemy>, Option<&'static Bullet>);
+
+//despawn only objects that are outside the arena and still moving away from it,
+//so enemies spawned above the screen are not culled before they fly in
+pub fn despawn_out_of_bounds(mut commands: Commands, mut enemy_count: ResMut<EnemyCount>, mut bullet_pool: ResMut<BulletPool>, query: Query<Culled>){
+
+    let bound_x = ARENA_WIDTH / 2. + ARENA_DESPAWN_MARGIN;
+    let bound_y = ARENA_HEIGHT / 2. + ARENA_DESPAWN_MARGIN;
+
+    for (entity, movable, velocity, transform, enemy, bullet) in query.iter(){
+        if !movable.auto_despawn{
+            continue;
+        }
+
+        let position = transform.translation;
+        let leaving = (position.x > bound_x && velocity.x >= 0.)
+            || (position.x < -bound_x && velocity.x <= 0.)
+            || (position.y > bound_y && velocity.y >= 0.)
+            || (position.y < -bound_y && velocity.y <= 0.);
+
+        if !leaving{
+            continue;
+        }
+
+        if bullet.is_some(){
+            bullet_pool.release(&mut commands, entity);
+        }else{
+            commands.entity(entity).despawn();
+            if enemy.is_some(){
+                enemy_count.0 = enemy_count.0.saturating_sub(1);
+            }
+        }
+    }
+}
+
+pub fn animate_object(time: ScaledTime, texture_atlases: Res<Assets<TextureAtlas>>,mut query: Query<(&mut AnimationTimer, &mut TextureAtlasSprite, &Handle<TextureAtlas>,)>){
 
     for (mut timer, mut sprite, texture_atlas_handle) in query.iter_mut() {
         timer.tick(time.delta());
@@ -12,37 +142,101 @@ pub fn animate_object(time: Res<Time>, texture_atlases: Res<Assets<TextureAtlas>
     }
 }
 
-pub fn animate_explosion(mut commands: Commands, time: Res<Time>, mut query: Query<(Entity, &mut ExplosionTimer, &mut TextureAtlasSprite), With<Explosion>>){
+pub fn animate_explosion(mut commands: Commands, mut explosion_pool: ResMut<ExplosionPool>, time: ScaledTime, texture_atlases: Res<Assets<TextureAtlas>>, mut query: Query<(Entity, &mut ExplosionTimer, &mut TextureAtlasSprite, &Handle<TextureAtlas>), With<Explosion>>){
 
-        for (entity, mut timer, mut sprite) in query.iter_mut() {
+    for (entity, mut timer, mut sprite, texture_atlas_handle) in query.iter_mut() {
         timer.0.tick(time.delta());
-        if timer.0.finished() {
+        if timer.0.just_finished() {
+            let frame_count = texture_atlases.get(texture_atlas_handle).map_or(0, |atlas| atlas.textures.len());
+
             sprite.index += 1; // move to next sprite cell
-            if sprite.index >= 16 {
-                commands.entity(entity).despawn()
+            if sprite.index >= frame_count {
+                explosion_pool.release(&mut commands, entity);
             }
         }
     }
 }
 
-pub fn explosion_spawn(mut commands: Commands, query: Query<(Entity, &ExplosionToSpawn)>, texture: Res<GameTextures>){
+pub fn explosion_spawn(mut commands: Commands, mut explosion_pool: ResMut<ExplosionPool>, mut sounds: ResMut<SoundQueue>, mut camera_effects: ResMut<CameraEffects>, mut explosions: EventReader<ExplosionToSpawn>, effects: Res<GameEffects>){
+
+    for explosion_to_spawn in explosions.iter(){
+
+        let effect = match effects.0.get(&explosion_to_spawn.1){
+            Some(effect) => effect,
+            None => continue,
+        };
 
-    for (explosion_entity, explosion_to_spawn) in query.iter(){
+        sounds.play(match explosion_to_spawn.1{
+            EffectKind::BulletHit => SoundEffect::Hit,
+            _ => SoundEffect::Explosion,
+        });
 
-        commands.spawn_bundle(SpriteSheetBundle{
-            texture_atlas: texture.explosion.clone(),
+        camera_effects.add_trauma(effect.trauma);
+        camera_effects.hit_stop(effect.hit_stop);
+
+        if let Some(emitter) = &effect.particles{
+            spawn_particles(&mut commands, explosion_to_spawn.0.truncate(), emitter.burst(&mut thread_rng()));
+        }
+
+        let explosion = explosion_pool.acquire(&mut commands);
+        commands.entity(explosion).insert_bundle(SpriteSheetBundle{
+            texture_atlas: effect.atlas.clone(),
+            sprite: TextureAtlasSprite{
+                color: effect.tint,
+                ..default()
+            },
             transform: Transform{
-                translation: explosion_to_spawn.0,
-                scale: Vec3::new(explosion_to_spawn.1, explosion_to_spawn.1, 1.),
+                translation: explosion_to_spawn.0.truncate().extend(effect.z_layer),
+                scale: Vec3::new(effect.scale, effect.scale, 1.),
                 ..default()
             },
             ..default()
         })
         .insert(Explosion)
-        .insert(ExplosionTimer::default())
-        .insert(AnimationTimer(Timer::from_seconds(0.1, true)));
-
-        commands.entity(explosion_entity).despawn();
+        .insert(ExplosionTimer(Timer::from_seconds(effect.frame_duration, true)));
     }
 }
 
+pub fn setup_pool_diagnostics(mut diagnostics: ResMut<Diagnostics>){
+    diagnostics.add(Diagnostic::new(BULLET_POOL_SIZE, "bullet_pool_size", 20));
+    diagnostics.add(Diagnostic::new(BULLET_POOL_HIGH_WATER_MARK, "bullet_pool_high_water_mark", 20));
+    diagnostics.add(Diagnostic::new(EXPLOSION_POOL_SIZE, "explosion_pool_size", 20));
+    diagnostics.add(Diagnostic::new(EXPLOSION_POOL_HIGH_WATER_MARK, "explosion_pool_high_water_mark", 20));
+}
+
+//released entities become reusable once the commands that stripped them have been applied
+pub fn recycle_pools(mut bullet_pool: ResMut<BulletPool>, mut explosion_pool: ResMut<ExplosionPool>){
+    bullet_pool.recycle();
+    explosion_pool.recycle();
+}
+
+pub fn pool_diagnostics(mut diagnostics: ResMut<Diagnostics>, bullet_pool: Res<BulletPool>, explosion_pool: Res<ExplosionPool>){
+    let bullets = bullet_pool.metrics();
+    diagnostics.add_measurement(BULLET_POOL_SIZE, bullets.size as f64);
+    diagnostics.add_measurement(BULLET_POOL_HIGH_WATER_MARK, bullets.high_water_mark as f64);
+
+    let explosions = explosion_pool.metrics();
+    diagnostics.add_measurement(EXPLOSION_POOL_SIZE, explosions.size as f64);
+    diagnostics.add_measurement(EXPLOSION_POOL_HIGH_WATER_MARK, explosions.high_water_mark as f64);
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn comments_and_blank_lines_are_skipped(){
+        let contents = "# header\n\n  first = 1\n   \n#second = 2\nthird=3\n";
+        assert_eq!(key_values(contents).collect::<Vec<_>>(), vec![("first", "1"), ("third", "3")]);
+    }
+
+    #[test]
+    fn lines_without_a_value_are_skipped(){
+        assert_eq!(key_values("no value here\nkey = \n").collect::<Vec<_>>(), vec![("key", "")]);
+    }
+
+    #[test]
+    fn values_keep_everything_after_the_first_equals(){
+        assert_eq!(key_values("title = a = b | c\n").collect::<Vec<_>>(), vec![("title", "a = b | c")]);
+    }
+}
@@ -0,0 +1,80 @@
+use std::time::{Duration, Instant};
+use bevy::prelude::*;
+use bevy::core::DefaultTaskPoolOptions;
+use bevy::log::LogPlugin;
+use bevy::render::settings::WgpuSettings;
+use space_shooter::*;
+
+//assets are loaded on the io task pool, the app is stepped until they are in or this runs out
+const LOADING_TIMEOUT: Duration = Duration::from_secs(10);
+
+//the whole game without a window, a gpu or a sound device, on a fixed clock
+fn headless_app() -> App{
+    let mut app = App::new();
+    app
+    .insert_resource(WgpuSettings { backends: None, ..default() })
+    .insert_resource(DefaultTaskPoolOptions::with_num_threads(1))
+    //the log subscriber is global and the tests share the process
+    .add_plugins_with(DefaultPlugins, |group| {
+        group.disable::<LogPlugin>();
+        #[cfg(feature = "render")]
+        group.disable::<bevy::winit::WinitPlugin>().disable::<bevy::gilrs::GilrsPlugin>();
+        #[cfg(feature = "audio")]
+        group.disable::<bevy::audio::AudioPlugin>();
+        group
+    })
+    .add_plugin(SpaceShooterPlugin { audio: AudioBackend::Null, seed: 7 })
+    //the autopilot plays, so the run is not counted in the stats file
+    .insert_resource(Autopilot { enabled: true, ..default() })
+    .insert_resource(GameClock::fixed(TIME_STEP));
+    app
+}
+
+fn current_state(app: &App) -> GameState{
+    app.world.resource::<State<GameState>>().current().clone()
+}
+
+fn step_until_main_menu(app: &mut App){
+    let started = Instant::now();
+    while current_state(app) == GameState::Loading{
+        assert!(started.elapsed() < LOADING_TIMEOUT, "assets did not finish loading");
+        app.update();
+    }
+    assert_eq!(current_state(app), GameState::MainMenu);
+}
+
+fn start_run(app: &mut App){
+    let _ = app.world.resource_mut::<State<GameState>>().set(GameState::Playing);
+    app.update();
+    assert_eq!(current_state(app), GameState::Playing);
+}
+
+#[test]
+fn loads_assets_and_reaches_the_main_menu(){
+    let mut app = headless_app();
+    step_until_main_menu(&mut app);
+
+    assert!(app.world.get_resource::<GameTextures>().is_some());
+    assert_eq!(app.world.resource::<GameClock>().seconds_since_startup(), 0.);
+}
+
+#[test]
+fn a_run_spawns_the_player_and_advances_the_clock(){
+    let mut app = headless_app();
+    step_until_main_menu(&mut app);
+    start_run(&mut app);
+
+    let started = app.world.resource::<GameClock>().seconds_since_startup();
+    let frames = 120;
+    for frame in 0..frames{
+        app.update();
+        //the player is spawned on a half second tick, checked before anything can reach it
+        if frame == 40{
+            let players = app.world.query_filtered::<(), With<Player>>().iter(&app.world).count();
+            assert_eq!(players, 1);
+        }
+    }
+    let elapsed = app.world.resource::<GameClock>().seconds_since_startup() - started;
+    assert!((elapsed - frames as f64 * TIME_STEP as f64).abs() < 1e-3, "clock advanced {} seconds", elapsed);
+    assert_eq!(current_state(&app), GameState::Playing);
+}